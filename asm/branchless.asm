@@ -0,0 +1,14 @@
+.global _start
+
+.section .text
+
+_start:
+    MOV r0, #100;
+_loop:
+    SUB r0, r0, #1;
+    TST r0, #1;
+    CINC r2, r2, NE;
+    CINC r3, r3, EQ;
+    CBNZ r0, _loop;
+    PRINTR r2;
+    PRINTR r3;
@@ -8,11 +8,11 @@
 .text
 
 _start:
-    MOV r0, =var_a;
+    ADR r0, var_a;
     LDR r0, [r0];
-    MOV r1, =var_b;
+    ADR r1, var_b;
     LDR r1, [r1];
     ADD r2, r0, r1;
-    MOV r0, =var_c;
+    ADR r0, var_c;
     STR r2, [r0];
     PRINTR r0;
\ No newline at end of file
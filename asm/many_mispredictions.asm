@@ -2,21 +2,17 @@
 
 .section .text
 
-_print_one:
-    MOV r3, #1;
-    PRINTR r3;
-    BX lr;
-
-_print_zero:
-    MOV r3, #0;
-    PRINTR r3;
-    BX lr;
-
 _start:
-    MOV r0, #10;
-    _loop:
+    MOV r0, #100;
+_loop:
     SUB r0, r0, #1;
-    MOV r1, r1, #2;
-    CBZ r1, _print_zero;
-    CBNZ r1, _print_one;
+    AND r1, r0, #1;
+    CBZ r1, _even;
+    ADD r2, r2, #1;
+    B _next;
+_even:
+    ADD r3, r3, #1;
+_next:
     CBNZ r0, _loop;
+    PRINTR r2;
+    PRINTR r3;
@@ -10,9 +10,9 @@ generate_random:
 
 _start:
     MOV r0, #12345;
-    MOV r1, #1103515245;
+    LDR r1, =1103515245;
     MOV r2, #12345;
-    MOV r3, #80000000;
+    LDR r3, =80000000;
 
     BL generate_random;
     PRINTR r0;
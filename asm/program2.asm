@@ -6,5 +6,4 @@
 start:
     ADD r0, r0, #1;
     PRINTR r0;
-    PRINTR pc;
     B start;
\ No newline at end of file
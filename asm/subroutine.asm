@@ -11,13 +11,13 @@ _add_numbers:
     BX lr;
 
 _start:
-    MOV r0, =a1;
+    ADR r0, a1;
     LDR r0, [r0];
 
-    MOV r1, =a2;
+    ADR r1, a2;
     LDR r1, [r1];
 
-    MOV r3, =loop_count;
+    ADR r3, loop_count;
     LDR r3, [r3];
 _loop:
     BL _add_numbers;
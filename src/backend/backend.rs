@@ -1,14 +1,18 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::backend::execution_unit::{EUState, EUTable};
+use crate::backend::execution_unit::{EUState, EUTable, PortLookup};
+use crate::backend::latency_table::LatencyTable;
 use crate::backend::physical_register::PhysRegFile;
 use crate::backend::register_alias_table::RAT;
 use crate::backend::reorder_buffer::{ROB, ROBSlotState};
-use crate::backend::reservation_station::{RenamedRegister, RS, RSBranch, RSBranchTarget, RSDataProcessing, RSInstr, RSLoadStore, RSOperand2, RSPrintr, RSState, RSTable};
-use crate::cpu::{ArgRegFile, CPSR, CPUConfig, LR, PC, PerfCounters, Trace};
+use crate::backend::reservation_station::{RenamedRegister, RS, RSAddressingMode, RSBranch, RSBranchTarget, RSDataProcessing, RSInstr, RSLoadStore, RSOperand2, RSPrintr, RSState, RSTable};
+use crate::cpu::{ArgRegFile, CPSR, CPUConfig, GENERAL_ARG_REG_CNT, LR, PC, PerfCounters, RenameRecovery, SPECIAL_ARG_REG_CNT, Trace, UnitType, XZR};
+use crate::frontend::branch_predictor::BranchPredictor;
+use crate::frontend::branch_target_buffer::BTB;
 use crate::frontend::frontend::FrontendControl;
-use crate::instructions::instructions::{BranchTarget, ConditionCode, DWordType, Instr, InstrQueue, Opcode, Operand2, RegisterType};
+use crate::frontend::return_address_stack::ReturnAddressStack;
+use crate::instructions::instructions::{AddressingMode, BranchTarget, ConditionCode, DWordType, Instr, InstrQueue, is_acquire, Opcode, Operand2, reads_carry, RegisterType, sets_flags, unit_type};
 use crate::memory_subsystem::memory_subsystem::MemorySubsystem;
 
 pub struct CDBBroadcast {
@@ -24,18 +28,28 @@ pub(crate) struct Backend {
     rs_table: RSTable,
     phys_reg_file: Rc<RefCell<PhysRegFile>>,
     rat: RAT,
+    rename_recovery: RenameRecovery,
+    // the number of cycles the renaming is stalled because of a rob walk
+    rename_stall_cycles: u64,
     rob: Rc<RefCell<ROB>>,
     eu_table: EUTable,
+    latency_table: LatencyTable,
     trace: Trace,
     retire_n_wide: u8,
     dispatch_n_wide: u8,
     issue_n_wide: u8,
     cdb_broadcast_buffer: Rc<RefCell<Vec<CDBBroadcast>>>,
     pub(crate) exit: bool,
+    // the fault that halted the CPU; it is only set when the faulting instruction retires.
+    pub(crate) fault: Option<String>,
     perf_counters: Rc<RefCell<PerfCounters>>,
+    branch_predictor: Rc<RefCell<Box<dyn BranchPredictor>>>,
+    return_address_stack: Rc<RefCell<ReturnAddressStack>>,
+    btb: Rc<RefCell<BTB>>,
 }
 
 impl Backend {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         cpu_config: &CPUConfig,
         instr_queue: &Rc<RefCell<InstrQueue>>,
@@ -43,6 +57,9 @@ impl Backend {
         arch_reg_file: &Rc<RefCell<ArgRegFile>>,
         frontend_control: &Rc<RefCell<FrontendControl>>,
         perf_counters: &Rc<RefCell<PerfCounters>>,
+        branch_predictor: &Rc<RefCell<Box<dyn BranchPredictor>>>,
+        return_address_stack: &Rc<RefCell<ReturnAddressStack>>,
+        btb: &Rc<RefCell<BTB>>,
     ) -> Backend {
         let phys_reg_file = Rc::new(RefCell::new(PhysRegFile::new(cpu_config.phys_reg_count)));
         let broadcast_buffer = Rc::new(RefCell::new(Vec::with_capacity(cpu_config.eu_count as usize)));
@@ -54,8 +71,11 @@ impl Backend {
             arch_reg_file: Rc::clone(arch_reg_file),
             rs_table: RSTable::new(cpu_config.rs_count),
             phys_reg_file: Rc::clone(&phys_reg_file),
-            rat: RAT::new(cpu_config.phys_reg_count),
+            rat: RAT::new(GENERAL_ARG_REG_CNT + SPECIAL_ARG_REG_CNT),
+            rename_recovery: cpu_config.rename_recovery,
+            rename_stall_cycles: 0,
             rob: Rc::new(RefCell::new(ROB::new(cpu_config.rob_capacity))),
+            latency_table: LatencyTable::new(cpu_config),
             eu_table: EUTable::new(cpu_config, &memory_subsystem, &phys_reg_file, &perf_counters, &broadcast_buffer),
             retire_n_wide: cpu_config.retire_n_wide,
             dispatch_n_wide: cpu_config.dispatch_n_wide,
@@ -63,7 +83,11 @@ impl Backend {
             cdb_broadcast_buffer: Rc::clone(&broadcast_buffer),
             frontend_control: Rc::clone(frontend_control),
             exit: false,
+            fault: None,
             perf_counters: Rc::clone(perf_counters),
+            branch_predictor: Rc::clone(branch_predictor),
+            return_address_stack: Rc::clone(return_address_stack),
+            btb: Rc::clone(btb),
         }
     }
 
@@ -78,6 +102,10 @@ impl Backend {
     }
 
     // issues as many instructions from the instruction queue into the rob as possible.
+    pub(crate) fn port_names(&self) -> Vec<String> {
+        self.eu_table.port_names()
+    }
+
     fn cycle_issue(&mut self) {
         let mut perf_counters = self.perf_counters.borrow_mut();
         let mut instr_queue = self.instr_queue.borrow_mut();
@@ -117,6 +145,7 @@ impl Backend {
             }
 
             rob_slot.pc = instr_queue_slot.pc;
+            rob_slot.fetch_fault = instr_queue_slot.fetch_fault;
             rob_slot.state = ROBSlotState::ISSUED;
             rob_slot.instr = Some(instr);
             rob_slot.branch_target_predicted = branch_target_predicted;
@@ -134,12 +163,19 @@ impl Backend {
         let mut memory_subsystem = self.memory_subsystem.borrow_mut();
         let mut rob = self.rob.borrow_mut();
 
+        // the RAT is being rebuilt by a rob walk
+        if self.rename_stall_cycles > 0 {
+            self.rename_stall_cycles -= 1;
+            return;
+        }
+
         for _ in 0..self.issue_n_wide {
             if rob.seq_rs_allocated == rob.seq_issued || !self.rs_table.has_idle() {
                 break;
             }
 
-            let rob_slot_index = rob.to_index(rob.seq_rs_allocated);
+            let rob_seq = rob.seq_rs_allocated;
+            let rob_slot_index = rob.to_index(rob_seq);
             let rob_slot = rob.get_mut(rob_slot_index);
 
             debug_assert!(rob_slot.state == ROBSlotState::ISSUED);
@@ -149,13 +185,26 @@ impl Backend {
             let instr = rob_slot.instr.as_ref().unwrap();
 
             if let Instr::LoadStore(load_store) = instr.as_ref() {
-                if load_store.opcode == Opcode::STR {
-                    if !memory_subsystem.sb.has_space() {
-                        // we can't allocate a slot in the store buffer, we are done
+                if unit_type(load_store.opcode) == UnitType::Store {
+                    let sb_cnt = if load_store.rd2.is_some() { 2 } else { 1 };
+                    if !memory_subsystem.sb.has_space_for(sb_cnt) {
+                        // we can't allocate the slots in the store buffer, we are done
+                        break;
+                    }
+
+                    rob_slot.sb_pos = Some(memory_subsystem.sb.allocate(load_store.opcode == Opcode::STLR));
+                    if load_store.rd2.is_some() {
+                        rob_slot.sb_pos2 = Some(memory_subsystem.sb.allocate(false));
+                    }
+                } else {
+                    if !memory_subsystem.lq.has_space() {
+                        // we can't allocate a slot in the load queue, we are done
                         break;
                     }
 
-                    rob_slot.sb_pos = Some(memory_subsystem.sb.allocate());
+                    // the load queue remembers which stores are older than the load
+                    let sb_tail = memory_subsystem.sb.tail();
+                    rob_slot.lq_pos = Some(memory_subsystem.lq.allocate(sb_tail, is_acquire(load_store.opcode)));
                 }
             }
 
@@ -188,12 +237,11 @@ impl Backend {
                             } else {
                                 None
                             },
-                            cpsr: if data_processing.condition == ConditionCode::AL {
+                            cpsr: if data_processing.condition == ConditionCode::AL && !reads_carry(data_processing.opcode) {
                                 None
                             } else {
                                 Some(register_rename_src(CPSR, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file))
                             },
-                            rd: register_rename_sink(data_processing.rd, &mut phys_reg_file, &mut self.rat),
                             operand2: match data_processing.operand2 {
                                 Operand2::Unused() => RSOperand2::Unused(),
                                 Operand2::Register { reg_id: register } => {
@@ -201,8 +249,39 @@ impl Backend {
                                         register: register_rename_src(register, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file)
                                     }
                                 }
+                                Operand2::ShiftedRegister { reg_id: register, shift_type, amount } => {
+                                    RSOperand2::ShiftedRegister {
+                                        register: register_rename_src(register, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file),
+                                        shift_type,
+                                        amount,
+                                    }
+                                }
+                                Operand2::ExtendedRegister { reg_id: register, extend_type, amount } => {
+                                    RSOperand2::ExtendedRegister {
+                                        register: register_rename_src(register, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file),
+                                        extend_type,
+                                        amount,
+                                    }
+                                }
                                 Operand2::Immediate { value } => RSOperand2::Immediate { value },
+                                Operand2::ShiftedImmediate { value, shift } => RSOperand2::ShiftedImmediate { value, shift },
+                                Operand2::Bitfield { lsb, width } => RSOperand2::Bitfield { lsb, width },
+                            },
+                            ra: if let Some(ra) = data_processing.ra {
+                                Some(register_rename_src(ra, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file))
+                            } else {
+                                None
                             },
+                            // the sink is renamed after the sources, otherwise a source that is also the
+                            // destination would refer to the new physical register.
+                            rd: register_rename_sink(data_processing.rd, &mut phys_reg_file, &mut self.rat, &mut rob_slot.renamed_registers),
+                            cpsr_dst: if sets_flags(data_processing.opcode) {
+                                Some(register_rename_sink(CPSR, &mut phys_reg_file, &mut self.rat, &mut rob_slot.renamed_registers))
+                            } else {
+                                None
+                            },
+                            nzcv: data_processing.nzcv,
+                            w_view: data_processing.w_view,
                         }
                     };
                 }
@@ -210,7 +289,7 @@ impl Backend {
                     rs.instr = RSInstr::Branch {
                         branch: RSBranch {
                             opcode: branch.opcode,
-                            condition: ConditionCode::AL,
+                            condition: branch.condition,
                             target: match branch.target {
                                 BranchTarget::Immediate { offset } => {
                                     RSBranchTarget::Immediate { offset }
@@ -226,32 +305,65 @@ impl Backend {
                             } else {
                                 None
                             },
+                            bit: branch.bit,
                             lr: if branch.link_bit {
-                                Some(register_rename_sink(LR as RegisterType, &mut phys_reg_file, &mut self.rat))
+                                Some(register_rename_sink(LR as RegisterType, &mut phys_reg_file, &mut self.rat, &mut rob_slot.renamed_registers))
                             } else {
                                 None
                             },
                         },
+                    };
+
+                    // if the branch turns out to be mispredicted, the RAT and the free list are restored from this checkpoint
+                    if self.rename_recovery == RenameRecovery::Checkpoint {
+                        self.rat.checkpoint(rob_seq);
+                        phys_reg_file.checkpoint(rob_seq);
                     }
                 }
                 Instr::LoadStore(load_store) => {
-                    match load_store.opcode {
-                        Opcode::LDR => rs.instr = RSInstr::LoadStore {
+                    match unit_type(load_store.opcode) {
+                        UnitType::Load => rs.instr = RSInstr::LoadStore {
                             load_store: RSLoadStore {
                                 opcode: load_store.opcode,
                                 condition: load_store.condition,
                                 rn: register_rename_src(load_store.rn, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file),
-                                rd: register_rename_sink(load_store.rd, &mut phys_reg_file, &mut self.rat),
-                                offset: load_store.offset,
+                                addressing_mode: addressing_mode_rename_src(load_store.addressing_mode, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file),
+                                rd: register_rename_sink(load_store.rd, &mut phys_reg_file, &mut self.rat, &mut rob_slot.renamed_registers),
+                                rd2: if let Some(rd2) = load_store.rd2 {
+                                    Some(register_rename_sink(rd2, &mut phys_reg_file, &mut self.rat, &mut rob_slot.renamed_registers))
+                                } else {
+                                    None
+                                },
+                                rn_wb: if load_store.addressing_mode.is_writeback() {
+                                    Some(register_rename_sink(load_store.rn, &mut phys_reg_file, &mut self.rat, &mut rob_slot.renamed_registers))
+                                } else {
+                                    None
+                                },
+                                data_type: load_store.data_type,
+                                signed: load_store.signed,
+                                w_view: load_store.w_view,
                             }
                         },
-                        Opcode::STR => rs.instr = RSInstr::LoadStore {
+                        UnitType::Store => rs.instr = RSInstr::LoadStore {
                             load_store: RSLoadStore {
                                 opcode: load_store.opcode,
                                 condition: load_store.condition,
                                 rn: register_rename_src(load_store.rn, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file),
                                 rd: register_rename_src(load_store.rd, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file),
-                                offset: load_store.offset,
+                                rd2: if let Some(rd2) = load_store.rd2 {
+                                    Some(register_rename_src(rd2, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file))
+                                } else {
+                                    None
+                                },
+                                addressing_mode: addressing_mode_rename_src(load_store.addressing_mode, rs, &mut self.rat, &arch_reg_file, &mut phys_reg_file),
+                                rn_wb: if load_store.addressing_mode.is_writeback() {
+                                    Some(register_rename_sink(load_store.rn, &mut phys_reg_file, &mut self.rat, &mut rob_slot.renamed_registers))
+                                } else {
+                                    None
+                                },
+                                data_type: load_store.data_type,
+                                signed: load_store.signed,
+                                w_view: load_store.w_view,
                             }
                         },
                         _ => unreachable!(),
@@ -281,8 +393,13 @@ impl Backend {
         let mut perf_counters = self.perf_counters.borrow_mut();
         let mut rob = self.rob.borrow_mut();
 
-        for _ in 0..self.dispatch_n_wide {
-            if !self.rs_table.has_ready() || !self.eu_table.has_idle() {
+        self.eu_table.reset_ports();
+
+        // Every ready instruction is considered at most once per cycle. An instruction that can't
+        // be dispatched because there is no free compatible port, is moved to the back of the ready queue.
+        let mut dispatched = 0;
+        for _ in 0..self.rs_table.ready_cnt() {
+            if dispatched == self.dispatch_n_wide || !self.eu_table.has_idle() {
                 break;
             }
 
@@ -296,16 +413,32 @@ impl Backend {
             //println!("{:?}",rob_slot.state);
             debug_assert!(rob_slot.state == ROBSlotState::STAGED);
 
-            let eu_index = self.eu_table.allocate();
+            let instr = rob_slot.instr.as_ref().unwrap();
+            let opcode = instr.opcode();
+            let unit_type = unit_type(opcode);
+
+            let port = match self.eu_table.find_port(unit_type, self.latency_table.is_pipelined(opcode)) {
+                PortLookup::Found(port) => port,
+                PortLookup::Busy => {
+                    // the unit isn't pipelined and is still busy with an older instruction
+                    perf_counters.unit_busy_cnt += 1;
+                    self.rs_table.enqueue_replay(rs_index);
+                    continue;
+                }
+                PortLookup::Conflict => {
+                    perf_counters.port_conflict_cnt += 1;
+                    self.rs_table.enqueue_replay(rs_index);
+                    continue;
+                }
+            };
+
+            let eu_index = self.eu_table.allocate(port);
             let eu = self.eu_table.get_mut(eu_index);
             debug_assert!(eu.state == EUState::EXECUTING);
 
-            let instr = rob_slot.instr.as_ref().unwrap();
-
             eu.rs_index = Some(rs_index);
-
-            // todo: correctly configure the cycles
-            eu.cycles_remaining = 1;
+            eu.unit_type = Some(unit_type);
+            eu.cycles_remaining = self.latency_table.latency(opcode);
 
             rob_slot.state = ROBSlotState::DISPATCHED;
             rob_slot.eu_index = Some(eu_index);
@@ -314,10 +447,15 @@ impl Backend {
                 println!("Dispatched [{}]", instr);
             }
             perf_counters.dispatch_cnt += 1;
+            perf_counters.port_dispatch_cnt[port as usize] += 1;
+            dispatched += 1;
         }
     }
 
     fn cycle_eu_table(&mut self) {
+        // the rob sequence of the oldest branch that completed this cycle and was mispredicted
+        let mut mispredicted_seq: Option<u64> = None;
+
         let mut rob = self.rob.borrow_mut();
         // todo: we should only iterate over the used execution units.
         for eu_index in 0..self.eu_table.capacity {
@@ -347,6 +485,18 @@ impl Backend {
                 continue;
             }
 
+            if eu.state == EUState::REPLAY {
+                // The instruction couldn't complete (e.g. a load waiting for an older store
+                // to resolve its address). The EU is released and the instruction is sent
+                // back to the ready queue so it gets dispatched again.
+                let eu_index = eu.index;
+                self.eu_table.deallocate(eu_index);
+                rob_slot.eu_index = None;
+                rob_slot.state = ROBSlotState::STAGED;
+                self.rs_table.enqueue_replay(rs_index);
+                continue;
+            }
+
             debug_assert!(eu.state == EUState::COMPLETED);
 
             let eu_index = eu.index;
@@ -357,7 +507,144 @@ impl Backend {
             rob_slot.rs_index = None;
 
             rob_slot.state = ROBSlotState::EXECUTED;
+
+            if let Instr::Branch(_) = rob_slot.instr.as_ref().unwrap().as_ref() {
+                if rob_slot.branch_target_actual != rob_slot.branch_target_predicted {
+                    let seq = rob.to_seq(rob_index);
+                    if mispredicted_seq.is_none_or(|s| seq < s) {
+                        mispredicted_seq = Some(seq);
+                    }
+                }
+            }
+        }
+
+        drop(rob);
+
+        if let Some(branch_seq) = mispredicted_seq {
+            let branch_target_actual = {
+                let mut rob = self.rob.borrow_mut();
+                let rob_slot_index = rob.to_index(branch_seq);
+                rob.get_mut(rob_slot_index).branch_target_actual
+            };
+            self.perf_counters.borrow_mut().branch_squash_cnt += 1;
+            self.flush(branch_seq + 1, branch_target_actual);
+        }
+    }
+
+    // Flushes the instruction with the given rob sequence and all younger instructions, and
+    // re-steers the frontend to the given pc. The instructions before it are not affected.
+    //
+    // If the flush is directly after a branch and checkpoints are enabled, the RAT and the free
+    // list are restored from the checkpoint of the branch. Otherwise the RAT is rebuilt by walking
+    // the remaining rob slots, which stalls the renaming.
+    fn flush(&mut self, rob_seq: u64, pc: usize) {
+        let mut perf_counters = self.perf_counters.borrow_mut();
+        let mut rob = self.rob.borrow_mut();
+        let mut phys_reg_file = self.phys_reg_file.borrow_mut();
+        let mut memory_subsystem = self.memory_subsystem.borrow_mut();
+
+        perf_counters.pipeline_flushes += 1;
+
+        if self.trace.pipeline_flush {
+            println!("Pipeline flush from rob seq {}; restart at pc {}", rob_seq, pc);
+        }
+
+        let restore_checkpoint = self.rename_recovery == RenameRecovery::Checkpoint && rob_seq > rob.head && {
+            let rob_slot_index = rob.to_index(rob_seq - 1);
+            matches!(rob.get_mut(rob_slot_index).instr.as_ref().unwrap().as_ref(), Instr::Branch(_))
+        };
+
+        // release the resources of the flushed instructions; from young to old so that the
+        // store buffer and load queue can be squashed from their tail.
+        for seq in (rob_seq..rob.tail).rev() {
+            let rob_slot_index = rob.to_index(seq);
+            let rob_slot = rob.get_mut(rob_slot_index);
+
+            if let Some(eu_index) = rob_slot.eu_index {
+                self.eu_table.deallocate(eu_index);
+            }
+
+            if let Some(rs_index) = rob_slot.rs_index {
+                self.rs_table.squash(rs_index);
+            }
+
+            if !restore_checkpoint {
+                for renamed_register in &rob_slot.renamed_registers {
+                    phys_reg_file.squash(renamed_register.phys_reg.unwrap());
+                }
+            }
+
+            // the second slot of a store pair is the youngest
+            if let Some(sb_pos2) = rob_slot.sb_pos2 {
+                memory_subsystem.sb.squash(sb_pos2);
+            }
+
+            if let Some(sb_pos) = rob_slot.sb_pos {
+                memory_subsystem.sb.squash(sb_pos);
+            }
+
+            if let Some(lq_pos) = rob_slot.lq_pos {
+                memory_subsystem.lq.squash(lq_pos);
+            }
+
+            perf_counters.bad_speculation_cnt += 1;
+        }
+        rob.flush(rob_seq);
+
+        if restore_checkpoint {
+            self.rat.restore(rob_seq - 1);
+            phys_reg_file.restore(rob_seq - 1);
+        } else {
+            self.rat.flush(rob_seq);
+            phys_reg_file.flush(rob_seq);
+            for seq in rob.head..rob_seq {
+                let rob_slot_index = rob.to_index(seq);
+                for renamed_register in &rob.get_mut(rob_slot_index).renamed_registers {
+                    self.rat.update(renamed_register.arch_reg, renamed_register.phys_reg.unwrap());
+                }
+            }
+
+            // the walk processes retire_n_wide rob slots per cycle
+            let walked = rob_seq - rob.head;
+            perf_counters.rob_walk_cnt += walked;
+            self.rename_stall_cycles = walked.div_ceil(self.retire_n_wide as u64);
+        }
+
+        // the instructions in the instruction queue are all younger
+        self.instr_queue.borrow_mut().flush();
+        let mut frontend_control = self.frontend_control.borrow_mut();
+        frontend_control.exit = false;
+        frontend_control.fetch_fault = false;
+
+        // The speculative state of the branch predictor and return address stack is rebuilt
+        // from the retired state and the branches that are still in flight.
+        let mut branch_predictor = self.branch_predictor.borrow_mut();
+        let mut return_address_stack = self.return_address_stack.borrow_mut();
+        branch_predictor.flush();
+        return_address_stack.flush();
+        for seq in rob.head..rob_seq {
+            let rob_slot_index = rob.to_index(seq);
+            let rob_slot = rob.get_mut(rob_slot_index);
+            if let Instr::Branch(branch) = rob_slot.instr.as_ref().unwrap().as_ref() {
+                if branch.is_conditional() {
+                    let target = if rob_slot.state == ROBSlotState::EXECUTED {
+                        rob_slot.branch_target_actual
+                    } else {
+                        rob_slot.branch_target_predicted
+                    };
+                    branch_predictor.speculate(target != rob_slot.pc + 1);
+                }
+
+                if branch.link_bit {
+                    return_address_stack.push(rob_slot.pc + 1);
+                } else if branch.is_return() {
+                    return_address_stack.pop();
+                }
+            }
         }
+
+        // re-steer the frontend
+        self.arch_reg_file.borrow_mut().set_value(PC, pc as DWordType);
     }
 
     fn cdb_broadcast(&mut self) {
@@ -399,7 +686,9 @@ impl Backend {
                             };
                         };
 
-                        if let RSOperand2::Register { ref mut register } = &mut data_processing.operand2 {
+                        if let RSOperand2::Register { ref mut register } |
+                               RSOperand2::ShiftedRegister { ref mut register, .. } |
+                               RSOperand2::ExtendedRegister { ref mut register, .. } = &mut data_processing.operand2 {
                             if let Some(r) = register.phys_reg {
                                 if r == broadcast.phys_reg && register.value.is_none(){
                                     register.value = Some(broadcast.value);
@@ -429,6 +718,16 @@ impl Backend {
                                 }
                             };
                         };
+
+                        if let Some(ra) = &mut data_processing.ra {
+                            if let Some(r) = ra.phys_reg {
+                                if r == broadcast.phys_reg && ra.value.is_none(){
+                                    ra.value = Some(broadcast.value);
+                                    at_least_one_resolved = true;
+                                    rs.pending_cnt -= 1;
+                                }
+                            };
+                        };
                     }
                     RSInstr::Branch { branch } => {
                         if let RSBranchTarget::Register { register } = &mut branch.target {
@@ -460,7 +759,17 @@ impl Backend {
                             }
                         };
 
-                        if load_store.opcode == Opcode::STR {
+                        if let RSAddressingMode::RegisterOffset { ref mut rm, .. } = &mut load_store.addressing_mode {
+                            if let Some(r) = rm.phys_reg {
+                                if r == broadcast.phys_reg && rm.value.is_none() {
+                                    rm.value = Some(broadcast.value);
+                                    at_least_one_resolved = true;
+                                    rs.pending_cnt -= 1;
+                                }
+                            };
+                        }
+
+                        if unit_type(load_store.opcode) == UnitType::Store {
                             if let Some(r) = load_store.rd.phys_reg {
                                 if r == broadcast.phys_reg && load_store.rd.value.is_none(){
                                     load_store.rd.value = Some(broadcast.value);
@@ -468,6 +777,16 @@ impl Backend {
                                     rs.pending_cnt -= 1;
                                 }
                             };
+
+                            if let Some(rd2) = &mut load_store.rd2 {
+                                if let Some(r) = rd2.phys_reg {
+                                    if r == broadcast.phys_reg && rd2.value.is_none(){
+                                        rd2.value = Some(broadcast.value);
+                                        at_least_one_resolved = true;
+                                        rs.pending_cnt -= 1;
+                                    }
+                                };
+                            }
                         }
                     }
                     RSInstr::Printr { printr } => {
@@ -493,7 +812,8 @@ impl Backend {
     }
 
     fn cycle_retire(&mut self) {
-        let mut bad_speculation = false;
+        // the rob sequence and pc of a load that violated the memory ordering
+        let mut violation: Option<(u64, usize)> = None;
 
         {
             let mut arch_reg_file = self.arch_reg_file.borrow_mut();
@@ -504,13 +824,43 @@ impl Backend {
             let mut rob = self.rob.borrow_mut();
 
             for _ in 0..self.retire_n_wide {
-                let rob_slot_index = rob.to_index(rob.seq_retired);
+                let rob_seq = rob.seq_retired;
+                let rob_slot_index = rob.to_index(rob_seq);
                 let rob_slot = rob.get_mut(rob_slot_index);
 
                 if rob_slot.state != ROBSlotState::EXECUTED {
                     break;
                 }
 
+                if rob_slot.fetch_fault {
+                    self.fault = Some(format!("Fetch fault: pc {} is outside of the program", rob_slot.pc));
+                    break;
+                }
+
+                if let Some(address) = rob_slot.alignment_fault {
+                    self.fault = Some(format!("Alignment fault: [{}] accessed unaligned address {}", rob_slot.instr.as_ref().unwrap(), address));
+                    break;
+                }
+
+                if let Some(address) = rob_slot.out_of_range_fault {
+                    self.fault = Some(format!("Out of range fault: [{}] accessed address {} outside of the memory of {} bytes",
+                                              rob_slot.instr.as_ref().unwrap(), address, memory_subsytem.memory.len()));
+                    break;
+                }
+
+                if let Some(lq_pos) = rob_slot.lq_pos {
+                    if memory_subsytem.lq.is_violated(lq_pos) {
+                        // The load has read a stale value because an older store to the same address
+                        // resolved after the load executed. The load and everything after it is
+                        // flushed and the frontend is re-steered to the load.
+                        perf_counters.memory_order_violation_cnt += 1;
+                        violation = Some((rob_seq, rob_slot.pc));
+                        break;
+                    }
+
+                    memory_subsytem.lq.deallocate(lq_pos);
+                }
+
                 let instr = rob_slot.instr.as_ref().unwrap();
 
                 perf_counters.retired_cnt += 1;
@@ -527,17 +877,11 @@ impl Backend {
 
                 // Update the architectural registers
                 for renamed_register in &rob_slot.renamed_registers {
-                    let rat_entry = self.rat.get_mut(renamed_register.arch_reg);
-                    debug_assert!(rat_entry.valid);
-
-                    let rat_phys_reg = rat_entry.phys_reg;
                     let rob_phys_reg = renamed_register.phys_reg.unwrap();
 
                     // only when the physical register on the rat is the same as the physical register used for that
                     // instruction, the rat entry should be invalidated
-                    if rat_phys_reg == rob_phys_reg {
-                        rat_entry.valid = false;
-                    }
+                    self.rat.retire(renamed_register.arch_reg, rob_phys_reg);
 
                     // update the architectural register
                     let value = phys_reg_file.get_value(rob_phys_reg);
@@ -551,15 +895,59 @@ impl Backend {
                     memory_subsytem.sb.commit(rob_slot.sb_pos.unwrap())
                 }
 
+                if let Some(sb_pos2) = rob_slot.sb_pos2 {
+                    memory_subsytem.sb.commit(sb_pos2)
+                }
+
                 // deal with any branch misprediction
-                if let Instr::Branch(_) = &instr.as_ref() {
+                if let Instr::Branch(branch) = &instr.as_ref() {
+                    self.rat.release(rob_seq);
+                    phys_reg_file.release(rob_seq);
+
+                    if branch.is_conditional() {
+                        let taken = rob_slot.branch_target_actual != rob_slot.pc + 1;
+                        let predicted_taken = rob_slot.branch_target_predicted != rob_slot.pc + 1;
+                        if taken == predicted_taken {
+                            perf_counters.branch_direction_good_cnt += 1;
+                        } else {
+                            perf_counters.branch_direction_miss_cnt += 1;
+                        }
+
+                        // train the predictor with the actual outcome
+                        self.branch_predictor.borrow_mut().update(rob_slot.pc, taken);
+                    }
+
+                    if branch.link_bit {
+                        if self.return_address_stack.borrow_mut().retire_push(rob_slot.pc + 1) {
+                            perf_counters.ras_overflow_cnt += 1;
+                        }
+                    } else if branch.is_return() {
+                        self.return_address_stack.borrow_mut().retire_pop();
+                        if rob_slot.branch_target_actual == rob_slot.branch_target_predicted {
+                            perf_counters.ras_hit_cnt += 1;
+                        } else {
+                            perf_counters.ras_miss_cnt += 1;
+                        }
+                    }
+
+                    if let BranchTarget::Register { .. } = branch.target {
+                        // the target of a BR and a BLR is predicted by the BTB
+                        if !branch.is_return() {
+                            if rob_slot.branch_target_actual == rob_slot.branch_target_predicted {
+                                perf_counters.btb_hit_cnt += 1;
+                            } else {
+                                perf_counters.btb_miss_cnt += 1;
+                            }
+                        }
+
+                        // returns are also recorded, so the BTB can be used when the return address stack is empty
+                        self.btb.borrow_mut().update(rob_slot.pc, rob_slot.branch_target_actual);
+                    }
+
                     if rob_slot.branch_target_actual != rob_slot.branch_target_predicted {
-                        // the branch was not correctly predicted
+                        // the branch was not correctly predicted; the younger instructions were
+                        // already squashed when the branch executed.
                         perf_counters.branch_miss_prediction_cnt += 1;
-                        bad_speculation = true;
-
-                        // re-steer the frontend
-                        arch_reg_file.set_value(PC, rob_slot.branch_target_actual as DWordType);
                     } else {
                         // the branch was correctly predicted
                         perf_counters.branch_good_predictions_cnt += 1;
@@ -568,37 +956,13 @@ impl Backend {
 
                 rob.seq_retired += 1;
                 rob.deallocate();
-
-                if bad_speculation {
-                    break;
-                }
             }
         }
 
-        if bad_speculation {
-            self.flush();
+        if let Some((rob_seq, pc)) = violation {
+            self.flush(rob_seq, pc);
         }
     }
-
-    fn flush(&mut self) {
-        let mut perf_counters = self.perf_counters.borrow_mut();
-        let mut rob = self.rob.borrow_mut();
-
-        if self.trace.pipeline_flush {
-            println!("Pipeline flush");
-        }
-
-        perf_counters.pipeline_flushes += 1;
-        perf_counters.bad_speculation_cnt += rob.size() as u64;
-
-        self.instr_queue.borrow_mut().flush();
-        self.phys_reg_file.borrow_mut().flush();
-        self.eu_table.flush();
-        rob.flush();
-        self.rat.flush();
-        self.rs_table.flush();
-        self.memory_subsystem.borrow_mut().sb.flush();
-    }
 }
 
 fn register_rename_src(arch_reg: RegisterType,
@@ -607,6 +971,11 @@ fn register_rename_src(arch_reg: RegisterType,
                        arch_reg_file: &ArgRegFile,
                        phys_reg_file: &mut PhysRegFile,
 ) -> RenamedRegister {
+    // the zero register has no physical register
+    if arch_reg == XZR {
+        return RenamedRegister { arch_reg, phys_reg: None, value: Some(0) };
+    }
+
     let mut phys_reg = None;
     let mut value = None;
     let rat_entry = rat.get(arch_reg);
@@ -635,13 +1004,41 @@ fn register_rename_src(arch_reg: RegisterType,
     RenamedRegister { arch_reg, phys_reg, value }
 }
 
+fn addressing_mode_rename_src(addressing_mode: AddressingMode,
+                              rs: &mut RS,
+                              rat: &mut RAT,
+                              arch_reg_file: &ArgRegFile,
+                              phys_reg_file: &mut PhysRegFile,
+) -> RSAddressingMode {
+    match addressing_mode {
+        AddressingMode::Offset { offset } => RSAddressingMode::Offset { offset },
+        AddressingMode::PreIndex { offset } => RSAddressingMode::PreIndex { offset },
+        AddressingMode::PostIndex { offset } => RSAddressingMode::PostIndex { offset },
+        AddressingMode::RegisterOffset { rm, shift } => RSAddressingMode::RegisterOffset {
+            rm: register_rename_src(rm, rs, rat, arch_reg_file, phys_reg_file),
+            shift,
+        },
+    }
+}
+
+// Renames the arch_reg to a newly allocated phys_reg. The renamed register is recorded in
+// renamed_registers of the rob slot so it can be retired or squashed.
+//
+// A write to the zero register is discarded, so it doesn't get a phys_reg.
 fn register_rename_sink(arch_reg: RegisterType,
                         phys_reg_file: &mut PhysRegFile,
                         rat: &mut RAT,
+                        renamed_registers: &mut Vec<RenamedRegister>,
 ) -> RenamedRegister {
+    if arch_reg == XZR {
+        return RenamedRegister { arch_reg, phys_reg: None, value: None };
+    }
+
     //println!("    register_rename_sink arch_reg={}", arch_reg);
     let phys_reg = phys_reg_file.allocate();
     rat.update(arch_reg, phys_reg);
 
-    RenamedRegister { arch_reg, phys_reg: Some(phys_reg), value: None }
+    let renamed_register = RenamedRegister { arch_reg, phys_reg: Some(phys_reg), value: None };
+    renamed_registers.push(renamed_register.clone());
+    renamed_register
 }
\ No newline at end of file
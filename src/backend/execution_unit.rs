@@ -4,23 +4,139 @@ use std::rc::Rc;
 use crate::backend::backend::CDBBroadcast;
 use crate::backend::physical_register::PhysRegFile;
 use crate::backend::reorder_buffer::ROBSlot;
-use crate::backend::reservation_station::{RS, RSBranch, RSDataProcessing, RSInstr, RSLoadStore, RSPrintr};
-use crate::cpu::{CARRY_FLAG, CPUConfig, NEGATIVE_FLAG, OVERFLOW_FLAG, PerfCounters, ZERO_FLAG};
-use crate::instructions::instructions::{ConditionCode, DWordType, Opcode, RegisterTypeDisplay};
-use crate::instructions::instructions::Opcode::LDR;
-use crate::memory_subsystem::memory_subsystem::MemorySubsystem;
+use crate::backend::reservation_station::{RS, RSAddressingMode, RSBranch, RSDataProcessing, RSInstr, RSLoadStore, RSOperand2, RSPrintr};
+use crate::cpu::{CARRY_FLAG, CPUConfig, MemoryDisambiguation, NEGATIVE_FLAG, OVERFLOW_FLAG, PerfCounters, UnitType, ZERO_FLAG};
+use crate::instructions::instructions::{ConditionCode, DWordType, Opcode, RegisterTypeDisplay, ShiftType, unit_type};
+use crate::memory_subsystem::memory_subsystem::{MemorySubsystem, read};
+use crate::memory_subsystem::store_buffer::SBLookup;
+
+fn is_conditional_select(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::CSEL | Opcode::CSINC | Opcode::CSINV | Opcode::CSNEG |
+                     Opcode::CSET | Opcode::CSETM | Opcode::CINC)
+}
+
+fn is_conditional_compare(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::CCMP | Opcode::CCMN)
+}
+
+// Checks if the condition holds for the given flags.
+fn condition_holds(condition: ConditionCode, cpsr: DWordType) -> bool {
+    match condition {
+        ConditionCode::EQ =>
+            (cpsr >> ZERO_FLAG) & 0x1 == 1,
+        ConditionCode::NE =>
+            (cpsr >> ZERO_FLAG) & 0x1 == 0,
+        ConditionCode::CS =>
+            (cpsr >> CARRY_FLAG) & 0x1 == 1,
+        ConditionCode::CC =>
+            (cpsr >> CARRY_FLAG) & 0x1 == 0,
+        ConditionCode::MI =>
+            (cpsr >> NEGATIVE_FLAG) & 0x1 == 1,
+        ConditionCode::PL =>
+            (cpsr >> NEGATIVE_FLAG) & 0x1 == 0,
+        ConditionCode::VS =>
+            (cpsr >> OVERFLOW_FLAG) & 0x1 == 1,
+        ConditionCode::VC =>
+            (cpsr >> OVERFLOW_FLAG) & 0x1 == 0,
+        ConditionCode::HI =>
+            (cpsr >> CARRY_FLAG) & 0x1 == 1 && (cpsr >> ZERO_FLAG) & 0x1 == 0,
+        ConditionCode::LS =>
+            (cpsr >> CARRY_FLAG) & 0x1 == 0 || (cpsr >> ZERO_FLAG) & 0x1 == 1,
+        ConditionCode::GE =>
+            (cpsr >> NEGATIVE_FLAG) & 0x1 == (cpsr >> OVERFLOW_FLAG) & 0x1,
+        ConditionCode::LT =>
+            (cpsr >> NEGATIVE_FLAG) & 0x1 != (cpsr >> OVERFLOW_FLAG) & 0x1,
+        ConditionCode::GT =>
+            (cpsr >> ZERO_FLAG) & 0x1 == 0 && ((cpsr >> NEGATIVE_FLAG) & 0x1 == (cpsr >> OVERFLOW_FLAG) & 0x1),
+        ConditionCode::LE =>
+            (cpsr >> ZERO_FLAG) & 0x1 == 1 || ((cpsr >> NEGATIVE_FLAG) & 0x1 != (cpsr >> OVERFLOW_FLAG) & 0x1),
+        ConditionCode::AL => true,
+    }
+}
+
+const NZCV_MASK: DWordType = (1 << NEGATIVE_FLAG) | (1 << ZERO_FLAG) | (1 << CARRY_FLAG) | (1 << OVERFLOW_FLAG);
+
+// The negative and zero flags of the result.
+fn nz_flags(result: DWordType, w_view: bool) -> DWordType {
+    // the 32 bits result is moved to the upper half
+    let result = if w_view { result << 32 } else { result };
+    let mut flags = 0;
+    if result & (1 << 63) != 0 {
+        flags |= 1 << NEGATIVE_FLAG;
+    }
+    if result == 0 {
+        flags |= 1 << ZERO_FLAG;
+    }
+    flags
+}
+
+// Adds x, y and the carry and returns the result with the NZCV flags. The 32 bits variant is
+// calculated in the upper half, so the flags are those of the 32 bits result.
+fn add_with_carry(x: DWordType, y: DWordType, carry: bool, w_view: bool) -> (DWordType, DWordType) {
+    if w_view {
+        // the lower half of y propagates the carry into the upper half
+        let y_lower = if carry { 0xFFFF_FFFF } else { 0 };
+        let (result, flags) = add_with_carry(x << 32, (y << 32) | y_lower, carry, false);
+        return (result >> 32, flags);
+    }
+
+    let unsigned_sum = x as u128 + y as u128 + carry as u128;
+    let result = unsigned_sum as DWordType;
+
+    let mut flags = nz_flags(result, false);
+    if unsigned_sum >> 64 != 0 {
+        flags |= 1 << CARRY_FLAG;
+    }
+    // the operands have the same sign, but the result has a different sign
+    if ((x ^ result) & (y ^ result)) >> 63 != 0 {
+        flags |= 1 << OVERFLOW_FLAG;
+    }
+    (result, flags)
+}
+
+// The carry flag of the cpsr source.
+fn carry_flag(data_processing: &RSDataProcessing) -> bool {
+    let cpsr = data_processing.cpsr.as_ref().unwrap().value.unwrap();
+    (cpsr >> CARRY_FLAG) & 0x1 == 1
+}
+
+// The 32 bits variant only reads the lower 32 bits of the source registers.
+fn truncate_sources(data_processing: &mut RSDataProcessing) {
+    let operand2 = match &mut data_processing.operand2 {
+        RSOperand2::Register { register } |
+        RSOperand2::ShiftedRegister { register, .. } |
+        RSOperand2::ExtendedRegister { register, .. } => Some(register),
+        _ => None,
+    };
+
+    for register in [data_processing.rn.as_mut(), data_processing.rd_src.as_mut(), data_processing.ra.as_mut(), operand2].into_iter().flatten() {
+        register.value = register.value.map(|value| value as u32 as DWordType);
+    }
+}
+
+// Stores the flags in the cpsr destination of a flag setting instruction. ADC and SBC don't set flags.
+fn set_flags(data_processing: &mut RSDataProcessing, flags: DWordType) {
+    if let Some(cpsr_dst) = &mut data_processing.cpsr_dst {
+        cpsr_dst.value = Some(flags);
+    }
+}
 
 /// A single execution unit.
 pub(crate) struct EU {
     pub(crate) index: u8,
     pub(crate) rs_index: Option<u16>,
     pub(crate) cycles_remaining: u8,
+    // the type of unit the instruction being executed needs.
+    pub(crate) unit_type: Option<UnitType>,
+    // the port the instruction was dispatched to.
+    pub(crate) port: Option<u8>,
     pub(crate) state: EUState,
     pub(crate) broadcast_buffer: Rc<RefCell<Vec<CDBBroadcast>>>,
     memory_subsystem: Rc<RefCell<MemorySubsystem>>,
     perf_counters: Rc<RefCell<PerfCounters>>,
     phys_reg_file: Rc<RefCell<PhysRegFile>>,
     trace: bool,
+    memory_disambiguation: MemoryDisambiguation,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -28,6 +144,8 @@ pub(crate) enum EUState {
     IDLE,
     EXECUTING,
     COMPLETED,
+    // the instruction could not complete and needs to be dispatched again.
+    REPLAY,
 }
 
 #[allow(non_snake_case)]
@@ -35,6 +153,8 @@ impl EU {
     fn reset(&mut self) {
         self.rs_index = None;
         self.cycles_remaining = 0;
+        self.unit_type = None;
+        self.port = None;
         self.state = EUState::IDLE;
     }
 
@@ -71,57 +191,70 @@ impl EU {
     }
 
     fn execute_data_processing(&mut self, data_processing: &mut RSDataProcessing, rob_slot: &mut ROBSlot) {
+        if data_processing.w_view {
+            truncate_sources(data_processing);
+        }
+
         let should_execute = if data_processing.condition != ConditionCode::AL {
             let cpsr = data_processing.cpsr.as_ref().unwrap().value.unwrap();
-            match data_processing.condition {
-                ConditionCode::EQ =>
-                    (cpsr >> ZERO_FLAG) & 0x1 == 1,
-                ConditionCode::NE =>
-                    (cpsr >> ZERO_FLAG) & 0x1 == 0,
-                ConditionCode::CS =>
-                    (cpsr >> CARRY_FLAG) & 0x1 == 1,
-                ConditionCode::CC =>
-                    (cpsr >> CARRY_FLAG) & 0x1 == 0,
-                ConditionCode::MI =>
-                    (cpsr >> NEGATIVE_FLAG) & 0x1 == 1,
-                ConditionCode::PL =>
-                    (cpsr >> NEGATIVE_FLAG) & 0x1 == 0,
-                ConditionCode::VS =>
-                    (cpsr >> OVERFLOW_FLAG) & 0x1 == 1,
-                ConditionCode::VC =>
-                    (cpsr >> OVERFLOW_FLAG) & 0x1 == 0,
-                ConditionCode::HI =>
-                    (cpsr >> CARRY_FLAG) & 0x1 == 1 && (cpsr >> ZERO_FLAG) & 0x1 == 0,
-                ConditionCode::LS =>
-                    (cpsr >> CARRY_FLAG) & 0x1 == 0 || (cpsr >> ZERO_FLAG) & 0x1 == 1,
-                ConditionCode::GE =>
-                    (cpsr >> NEGATIVE_FLAG) & 0x1 == (cpsr >> OVERFLOW_FLAG) & 0x1,
-                ConditionCode::LT =>
-                    (cpsr >> NEGATIVE_FLAG) & 0x1 != (cpsr >> OVERFLOW_FLAG) & 0x1,
-                ConditionCode::GT =>
-                    (cpsr >> ZERO_FLAG) & 0x1 == 0 && ((cpsr >> NEGATIVE_FLAG) & 0x1 == (cpsr >> OVERFLOW_FLAG) & 0x1),
-                ConditionCode::LE =>
-                    (cpsr >> ZERO_FLAG) & 0x1 == 1 || ((cpsr >> NEGATIVE_FLAG) & 0x1 != (cpsr >> OVERFLOW_FLAG) & 0x1),
-                _ => false,
-            }
+            condition_holds(data_processing.condition, cpsr)
         } else {
             true
         };
 
-        let result = if should_execute {
+        let result = if is_conditional_select(data_processing.opcode) {
+            // the condition of a conditional select picks the value instead of predicating the instruction
+            self.execute_conditional_select(data_processing, should_execute)
+        } else if is_conditional_compare(data_processing.opcode) {
+            self.execute_conditional_compare(data_processing, should_execute)
+        } else if should_execute {
             match &data_processing.opcode {
                 Opcode::ADD => self.execute_ADD(data_processing),
+                Opcode::ADDS |
+                Opcode::ADC |
+                Opcode::ADCS => self.execute_add_with_carry(data_processing),
                 Opcode::SUB => self.execute_SUB(data_processing),
+                Opcode::SUBS |
+                Opcode::SBC |
+                Opcode::SBCS => self.execute_subtract_with_carry(data_processing),
                 Opcode::RSB => self.execute_RSB(data_processing),
                 Opcode::MUL => self.execute_MUL(data_processing),
-                Opcode::MOV => self.execute_MOV(data_processing),
+                Opcode::MADD |
+                Opcode::MSUB => self.execute_multiply_accumulate(data_processing),
+                Opcode::MNEG => self.execute_MNEG(data_processing),
+                Opcode::SMULL |
+                Opcode::UMULL |
+                Opcode::SMULH |
+                Opcode::UMULH => self.execute_wide_multiply(data_processing),
+                Opcode::MOV |
+                Opcode::MOVZ => self.execute_MOV(data_processing),
+                Opcode::MOVN => self.execute_MOVN(data_processing),
+                Opcode::ADR |
+                Opcode::ADRP => self.execute_ADR(data_processing, rob_slot),
+                Opcode::MOVK => self.execute_MOVK(data_processing),
                 Opcode::CMP => self.execute_CMP(data_processing),
                 Opcode::SDIV => self.execute_SDIV(data_processing),
+                Opcode::UDIV => self.execute_UDIV(data_processing),
                 Opcode::AND => self.execute_AND(data_processing),
+                Opcode::ANDS => self.execute_ANDS(data_processing),
                 Opcode::ORR => self.execute_ORR(data_processing),
                 Opcode::EOR => self.execute_EOR(data_processing),
+                Opcode::LSL |
+                Opcode::LSR |
+                Opcode::ASR |
+                Opcode::ROR => self.execute_shift(data_processing),
                 Opcode::NEG => self.execute_NEG(data_processing),
                 Opcode::MVN => self.execute_MVN(data_processing),
+                Opcode::UBFX |
+                Opcode::SBFX |
+                Opcode::BFI |
+                Opcode::BFXIL => self.execute_bitfield(data_processing),
+                Opcode::CLZ |
+                Opcode::CLS |
+                Opcode::RBIT |
+                Opcode::REV |
+                Opcode::REV16 |
+                Opcode::REV32 => self.execute_bit_manipulation(data_processing),
                 Opcode::TST => self.execute_TST(data_processing),
                 Opcode::TEQ => self.execute_TEQ(data_processing),
                 _ => unreachable!()
@@ -132,69 +265,126 @@ impl EU {
             data_processing.rd_src.as_ref().unwrap().value.unwrap()
         };
 
+        // a write to the W view zero extends the result
+        let result = if data_processing.w_view { result as u32 as DWordType } else { result };
+
         data_processing.rd.value = Some(result);
-        self.phys_reg_file.borrow_mut().set_value(data_processing.rd.phys_reg.unwrap(), result);
+        let mut phys_reg_file = self.phys_reg_file.borrow_mut();
 
-        rob_slot.renamed_registers.push(data_processing.rd.clone());
+        // a write to the zero register is discarded
+        if let Some(rd) = data_processing.rd.phys_reg {
+            phys_reg_file.set_value(rd, result);
+            self.broadcast_buffer.borrow_mut().push(CDBBroadcast { phys_reg: rd, value: result });
+        }
 
-        let mut phys_reg_file = self.phys_reg_file.borrow_mut();
-        let rd = data_processing.rd.phys_reg.unwrap();
-        let phys_reg_entry = phys_reg_file.get_mut(rd);
+        // the flag setting instructions also write the cpsr
+        if let Some(cpsr_dst) = &data_processing.cpsr_dst {
+            let phys_reg = cpsr_dst.phys_reg.unwrap();
+            let value = cpsr_dst.value.unwrap();
+            phys_reg_file.set_value(phys_reg, value);
+            self.broadcast_buffer.borrow_mut().push(CDBBroadcast { phys_reg, value });
+        }
+    }
 
-        self.broadcast_buffer.borrow_mut().push(CDBBroadcast { phys_reg: rd, value: phys_reg_entry.value });
+    fn execute_conditional_select(&mut self, data_processing: &mut RSDataProcessing, condition: bool) -> DWordType {
+        match data_processing.opcode {
+            Opcode::CSET => if condition { 1 } else { 0 },
+            Opcode::CSETM => if condition { DWordType::MAX } else { 0 },
+            Opcode::CINC => {
+                let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+                if condition { rn_value.wrapping_add(1) } else { rn_value }
+            }
+            _ => {
+                let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+                if condition {
+                    return rn_value;
+                }
+
+                let operand2_value = data_processing.operand2_value();
+                match data_processing.opcode {
+                    Opcode::CSEL => operand2_value,
+                    Opcode::CSINC => operand2_value.wrapping_add(1),
+                    Opcode::CSINV => !operand2_value,
+                    Opcode::CSNEG => operand2_value.wrapping_neg(),
+                    _ => unreachable!(),
+                }
+            }
+        }
     }
 
     fn execute_CMP(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
 
         let rd_value = data_processing.rd_src.as_ref().unwrap().value.unwrap();
 
         // Perform the comparison: rn - operand2
-        let result = rn_value.wrapping_sub(operand2_value);
+        let (_, flags) = add_with_carry(rn_value, !operand2_value, true, data_processing.w_view);
 
-        // Update the CPSR flags based on the result
-        let zero_flag = result == 0;
-        let negative_flag = (result & (1 << 63)) != 0;
-        let carry_flag = (rn_value as u128).wrapping_sub(operand2_value as u128) > (rn_value as u128); // Checking for borrow
-        let overflow_flag = (((rn_value ^ operand2_value) & (rn_value ^ result)) >> 63) != 0;
+        (rd_value & !NZCV_MASK) | flags
+    }
 
-        let mut rd_update = rd_value;
-        if zero_flag {
-            rd_update |= 1 << ZERO_FLAG;
-        } else {
-            rd_update &= !(1 << ZERO_FLAG);
-        }
+    // CCMP and CCMN compare if the condition holds; otherwise the flags are set to the nzcv immediate.
+    fn execute_conditional_compare(&mut self, data_processing: &mut RSDataProcessing, condition: bool) -> DWordType {
+        let rd_value = data_processing.rd_src.as_ref().unwrap().value.unwrap();
 
-        if negative_flag {
-            rd_update |= 1 << NEGATIVE_FLAG;
+        let flags = if condition {
+            let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+            let operand2_value = data_processing.operand2_value();
+            if data_processing.opcode == Opcode::CCMP {
+                add_with_carry(rn_value, !operand2_value, true, data_processing.w_view).1
+            } else {
+                add_with_carry(rn_value, operand2_value, false, data_processing.w_view).1
+            }
         } else {
-            rd_update &= !(1 << NEGATIVE_FLAG);
-        }
+            (data_processing.nzcv as DWordType) << OVERFLOW_FLAG
+        };
 
-        if carry_flag {
-            rd_update |= 1 << CARRY_FLAG;
-        } else {
-            rd_update &= !(1 << CARRY_FLAG);
-        }
+        (rd_value & !NZCV_MASK) | flags
+    }
 
-        if overflow_flag {
-            rd_update |= 1 << OVERFLOW_FLAG;
-        } else {
-            rd_update &= !(1 << OVERFLOW_FLAG);
-        }
+    // ADDS, ADC and ADCS
+    fn execute_add_with_carry(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        let operand2_value = data_processing.operand2_value();
+        let carry = data_processing.opcode != Opcode::ADDS && carry_flag(data_processing);
 
-        rd_update
+        let (result, flags) = add_with_carry(rn_value, operand2_value, carry, data_processing.w_view);
+        set_flags(data_processing, flags);
+        result
+    }
+
+    // SUBS, SBC and SBCS. A subtraction is an addition of the inverted operand2 and a carry
+    // of 1; so a carry flag of 0 means there was a borrow.
+    fn execute_subtract_with_carry(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        let operand2_value = data_processing.operand2_value();
+        let carry = data_processing.opcode == Opcode::SUBS || carry_flag(data_processing);
+
+        let (result, flags) = add_with_carry(rn_value, !operand2_value, carry, data_processing.w_view);
+        set_flags(data_processing, flags);
+        result
+    }
+
+    fn execute_ANDS(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        let operand2_value = data_processing.operand2_value();
+        let result = rn_value & operand2_value;
+
+        // the carry and overflow flags are cleared
+        set_flags(data_processing, nz_flags(result, data_processing.w_view));
+        result
     }
 
     fn execute_TST(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
 
         let result = rn_value & operand2_value;
 
         let zero_flag = result == 0;
-        let negative_flag = (result & (1 << 63)) != 0;
+        let sign_bit = if data_processing.w_view { 31 } else { 63 };
+        let negative_flag = (result >> sign_bit) & 0x1 == 1;
 
         let mut rd_update = data_processing.rd_src.as_ref().unwrap().value.unwrap();
 
@@ -215,12 +405,13 @@ impl EU {
 
     fn execute_TEQ(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
 
         let result = rn_value ^ operand2_value;
 
         let zero_flag = result == 0;
-        let negative_flag = (result & (1 << 63)) != 0;
+        let sign_bit = if data_processing.w_view { 31 } else { 63 };
+        let negative_flag = (result >> sign_bit) & 0x1 == 1;
 
         let mut rd_update = data_processing.rd_src.as_ref().unwrap().value.unwrap();
 
@@ -239,50 +430,120 @@ impl EU {
         rd_update
     }
 
+    // A division by zero gives 0.
     fn execute_SDIV(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        if data_processing.w_view {
+            let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap() as i32;
+            let operand2_value = data_processing.operand2_value() as i32;
+            return if operand2_value == 0 { 0 } else { rn_value.wrapping_div(operand2_value) as u32 as DWordType };
+        }
+
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap() as i64;
+        let operand2_value = data_processing.operand2_value() as i64;
+
+        if operand2_value == 0 {
+            0
+        } else {
+            // the overflow of i64::MIN / -1 wraps around to i64::MIN
+            rn_value.wrapping_div(operand2_value) as DWordType
+        }
+    }
+
+    // A division by zero gives 0.
+    fn execute_UDIV(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
 
-        rn_value / operand2_value
+        rn_value.checked_div(operand2_value).unwrap_or(0)
     }
 
     fn execute_MOV(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
-        data_processing.operand2.value()
+        data_processing.operand2_value()
+    }
+
+    fn execute_MOVN(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        !data_processing.operand2_value()
+    }
+
+    // Replaces 16 bits of the register and keeps the other bits.
+    fn execute_MOVK(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rd_value = data_processing.rd_src.as_ref().unwrap().value.unwrap();
+        let shift = match data_processing.operand2 {
+            RSOperand2::ShiftedImmediate { shift, .. } => shift,
+            _ => unreachable!(),
+        };
+
+        (rd_value & !(0xFFFF << shift)) | data_processing.operand2_value()
     }
 
     fn execute_MUL(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
         rn_value.wrapping_mul(operand2_value)
     }
 
+    // MADD and MSUB add the product to, or subtract it from, the accumulator.
+    fn execute_multiply_accumulate(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        let operand2_value = data_processing.operand2_value();
+        let ra_value = data_processing.ra.as_ref().unwrap().value.unwrap();
+
+        let product = rn_value.wrapping_mul(operand2_value);
+        if data_processing.opcode == Opcode::MADD {
+            ra_value.wrapping_add(product)
+        } else {
+            ra_value.wrapping_sub(product)
+        }
+    }
+
+    fn execute_MNEG(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        let operand2_value = data_processing.operand2_value();
+        rn_value.wrapping_mul(operand2_value).wrapping_neg()
+    }
+
+    // SMULL and UMULL multiply the lower 32 bits into a 64 bits product. SMULH and UMULH
+    // return the upper 64 bits of the 128 bits product.
+    fn execute_wide_multiply(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        let operand2_value = data_processing.operand2_value();
+
+        match data_processing.opcode {
+            Opcode::SMULL => ((rn_value as i32 as i64) * (operand2_value as i32 as i64)) as DWordType,
+            Opcode::UMULL => (rn_value as u32 as DWordType) * (operand2_value as u32 as DWordType),
+            Opcode::SMULH => (((rn_value as i64 as i128) * (operand2_value as i64 as i128)) >> 64) as DWordType,
+            Opcode::UMULH => (((rn_value as u128) * (operand2_value as u128)) >> 64) as DWordType,
+            _ => unreachable!(),
+        }
+    }
+
     fn execute_RSB(&mut self, data_processing: &mut RSDataProcessing) -> u64 {    // let rn = rs.source[0].value.unwrap();
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
         operand2_value.wrapping_sub(rn_value)
     }
 
     fn execute_SUB(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
         rn_value.wrapping_sub(operand2_value)
     }
 
     fn execute_ADD(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
         rn_value.wrapping_add(operand2_value)
     }
 
     fn execute_AND(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
         rn_value & operand2_value
     }
 
     fn execute_ORR(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
         rn_value | operand2_value
     }
 
@@ -291,66 +552,248 @@ impl EU {
         !rn_value
     }
 
+    fn execute_bitfield(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        let (lsb, width) = match data_processing.operand2 {
+            RSOperand2::Bitfield { lsb, width } => (lsb as u32, width as u32),
+            _ => unreachable!(),
+        };
+        let mask = DWordType::MAX >> (64 - width);
+
+        match data_processing.opcode {
+            Opcode::UBFX => (rn_value >> lsb) & mask,
+            // the bitfield is moved to the top, so the arithmetic shift extends its sign bit
+            Opcode::SBFX => (((rn_value << (64 - lsb - width)) as i64) >> (64 - width)) as DWordType,
+            Opcode::BFI => {
+                let rd_value = data_processing.rd_src.as_ref().unwrap().value.unwrap();
+                (rd_value & !(mask << lsb)) | ((rn_value & mask) << lsb)
+            }
+            Opcode::BFXIL => {
+                let rd_value = data_processing.rd_src.as_ref().unwrap().value.unwrap();
+                (rd_value & !mask) | ((rn_value >> lsb) & mask)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn execute_bit_manipulation(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+
+        if data_processing.w_view {
+            let rn_value = rn_value as u32;
+            let result = match data_processing.opcode {
+                Opcode::CLZ => rn_value.leading_zeros(),
+                Opcode::CLS => (rn_value ^ ((rn_value as i32 >> 1) as u32)).leading_zeros() - 1,
+                Opcode::RBIT => rn_value.reverse_bits(),
+                Opcode::REV => rn_value.swap_bytes(),
+                Opcode::REV16 => ((rn_value & 0x00FF00FF) << 8) | ((rn_value >> 8) & 0x00FF00FF),
+                _ => unreachable!(),
+            };
+            return result as DWordType;
+        }
+
+        match data_processing.opcode {
+            Opcode::CLZ => rn_value.leading_zeros() as DWordType,
+            // the number of bits below the sign bit that are equal to the sign bit
+            Opcode::CLS => (rn_value ^ ((rn_value as i64 >> 1) as DWordType)).leading_zeros() as DWordType - 1,
+            Opcode::RBIT => rn_value.reverse_bits(),
+            Opcode::REV => rn_value.swap_bytes(),
+            Opcode::REV16 => ((rn_value & 0x00FF00FF00FF00FF) << 8) | ((rn_value >> 8) & 0x00FF00FF00FF00FF),
+            Opcode::REV32 => rn_value.swap_bytes().rotate_left(32),
+            _ => unreachable!(),
+        }
+    }
+
     fn execute_EOR(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
-        let operand2_value = data_processing.operand2.value();
+        let operand2_value = data_processing.operand2_value();
         rn_value ^ operand2_value
     }
 
+    fn execute_shift(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
+        let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
+        // only the lowest 6 bits (5 bits for the 32 bits variant) of the shift amount are used
+        let amount = data_processing.operand2_value() as u8;
+        let shift_type = match data_processing.opcode {
+            Opcode::LSL => ShiftType::LSL,
+            Opcode::LSR => ShiftType::LSR,
+            Opcode::ASR => ShiftType::ASR,
+            Opcode::ROR => ShiftType::ROR,
+            _ => unreachable!(),
+        };
+
+        if data_processing.w_view {
+            shift_type.apply_w(rn_value, amount)
+        } else {
+            shift_type.apply(rn_value, amount)
+        }
+    }
+
     fn execute_NEG(&mut self, data_processing: &mut RSDataProcessing) -> DWordType {
         let rn_value = data_processing.rn.as_ref().unwrap().value.unwrap();
         rn_value.wrapping_neg()
     }
 
     fn execute_load_store(&mut self, load_store: &mut RSLoadStore, rob_slot: &mut ROBSlot) {
-        match &load_store.opcode {
-            Opcode::LDR => self.execute_LDR(load_store, rob_slot),
-            Opcode::STR => self.execute_STR(load_store, rob_slot),
+        let base = load_store.rn.value.unwrap();
+        let (address, writeback) = match &load_store.addressing_mode {
+            RSAddressingMode::Offset { offset } => (base.wrapping_add(*offset as DWordType), None),
+            RSAddressingMode::PreIndex { offset } => {
+                let address = base.wrapping_add(*offset as DWordType);
+                (address, Some(address))
+            }
+            RSAddressingMode::PostIndex { offset } => (base, Some(base.wrapping_add(*offset as DWordType))),
+            RSAddressingMode::RegisterOffset { rm, shift } => (base.wrapping_add(rm.value.unwrap() << shift), None),
+        };
+
+        let size = load_store.data_type.size();
+        if address % size as DWordType != 0 {
+            // the fault is raised when the instruction retires.
+            rob_slot.alignment_fault = Some(address);
+            return;
+        }
+
+        // a pair accesses the second value directly after the first one
+        let access_size = if load_store.rd2.is_some() { 2 * size } else { size };
+        let memory_size = self.memory_subsystem.borrow().memory.len() as DWordType;
+        let in_range = address.checked_add(access_size as DWordType).is_some_and(|end| end <= memory_size);
+        if !in_range {
+            // a load on a mispredicted path could have a garbage address; so just like for the
+            // alignment fault, the fault is raised when the instruction retires.
+            rob_slot.out_of_range_fault = Some(address);
+            return;
+        }
+
+        match unit_type(load_store.opcode) {
+            UnitType::Load => self.execute_load(load_store, address, rob_slot),
+            UnitType::Store => self.execute_store(load_store, address, rob_slot),
             _ => unreachable!()
         };
 
-        // todo: This is ugly because it couples to the LDR. Leads to problems when more loads are added
-        if load_store.opcode == LDR {
-            let mut phys_reg_file = self.phys_reg_file.borrow_mut();
-            let rd = load_store.rd.phys_reg.unwrap();
-            let phys_reg_entry = phys_reg_file.get_mut(rd);
-            self.broadcast_buffer.borrow_mut().push(CDBBroadcast { phys_reg: rd, value: phys_reg_entry.value });
+        if self.state == EUState::REPLAY {
+            return;
+        }
+
+        if let Some(value) = writeback {
+            let rn_wb = load_store.rn_wb.as_mut().unwrap();
+            let phys_reg = rn_wb.phys_reg.unwrap();
+            rn_wb.value = Some(value);
+            self.phys_reg_file.borrow_mut().set_value(phys_reg, value);
+            self.broadcast_buffer.borrow_mut().push(CDBBroadcast { phys_reg, value });
         }
     }
 
-    fn execute_STR(&mut self, load_store: &mut RSLoadStore, rob_slot: &mut ROBSlot) {
-        let value = load_store.rd.value.unwrap();
-        let address = load_store.rn.value.unwrap();
+    fn execute_store(&mut self, load_store: &mut RSLoadStore, address: DWordType, rob_slot: &mut ROBSlot) {
+        let size = load_store.data_type.size();
 
         let mut memory_subsystem = self.memory_subsystem.borrow_mut();
-        memory_subsystem.sb.store(rob_slot.sb_pos.unwrap(), address, value);
+        let sb_pos = rob_slot.sb_pos.unwrap();
+        memory_subsystem.sb.store(sb_pos, address, size, load_store.rd.value.unwrap());
+
+        // the second register of a store pair is stored directly after the first one.
+        if let Some(rd2) = &load_store.rd2 {
+            let sb_pos2 = rob_slot.sb_pos2.unwrap();
+            memory_subsystem.sb.store(sb_pos2, address + size as DWordType, size, rd2.value.unwrap());
+        }
+
+        // now the address is known, younger loads that executed too early can be detected.
+        let store_seq = memory_subsystem.sb.to_seq(sb_pos);
+        let store_size = if load_store.rd2.is_some() { 2 * size } else { size };
+        memory_subsystem.lq.store_resolved(store_seq, address, store_size);
     }
 
-    fn execute_LDR(&mut self, load_store: &mut RSLoadStore, rob_slot: &mut ROBSlot) {
-        let memory_subsystem = self.memory_subsystem.borrow_mut();
-        let address = load_store.rn.value.unwrap() as usize;
-        let value = memory_subsystem.memory[address];
+    fn execute_load(&mut self, load_store: &mut RSLoadStore, address: DWordType, rob_slot: &mut ROBSlot) {
+        let mut memory_subsystem = self.memory_subsystem.borrow_mut();
+        let size = load_store.data_type.size();
+        let lq_pos = rob_slot.lq_pos.unwrap();
+        let sb_tail = memory_subsystem.lq.sb_tail(lq_pos);
+        let speculative = self.memory_disambiguation == MemoryDisambiguation::Aggressive;
+
+        // a load can't be performed before an older acquire load
+        if memory_subsystem.lq.has_pending_acquire(lq_pos) {
+            self.perf_counters.borrow_mut().acquire_blocked_cnt += 1;
+            self.state = EUState::REPLAY;
+            return;
+        }
+
+        // LDAR can't be performed before an older release store is written to memory; LDAPR can
+        if load_store.opcode == Opcode::LDAR && memory_subsystem.sb.has_release(sb_tail) {
+            self.perf_counters.borrow_mut().release_blocked_cnt += 1;
+            self.state = EUState::REPLAY;
+            return;
+        }
+
+        // a load pair reads the second value directly after the first one
+        let cnt = if load_store.rd2.is_some() { 2 } else { 1 };
+        let mut values = [0; 2];
+        // the oldest store a value is forwarded from; None if any value is read from memory
+        let mut forwarded_from = None;
+        for k in 0..cnt {
+            let element_address = address + (k * size) as DWordType;
+
+            // Older stores that haven't been written to memory yet are still in the store buffer.
+            values[k as usize] = match memory_subsystem.sb.lookup(sb_tail, element_address, size, speculative) {
+                SBLookup::Forward { value, seq } => {
+                    self.perf_counters.borrow_mut().load_forwarded_cnt += 1;
+                    if k == 0 || forwarded_from.is_some_and(|s| seq < s) {
+                        forwarded_from = Some(seq);
+                    }
+                    value
+                }
+                SBLookup::Blocked => {
+                    // An older store has an unknown address or partially overlaps; it could alias with this load.
+                    self.perf_counters.borrow_mut().load_blocked_cnt += 1;
+                    self.state = EUState::REPLAY;
+                    return;
+                }
+                SBLookup::Miss => {
+                    forwarded_from = None;
+                    read(&memory_subsystem.memory, element_address, size)
+                }
+            };
+        }
+
+        memory_subsystem.lq.executed(lq_pos, address, cnt * size, forwarded_from);
+        drop(memory_subsystem);
+
+        // the value is zero extended; so only a signed load needs to extend the sign bit.
+        let value = if load_store.signed && size < 8 {
+            let shift = 64 - 8 * size as u32;
+            (((values[0] << shift) as i64) >> shift) as DWordType
+        } else {
+            values[0]
+        };
+        // a signed load into a W register only extends the sign bit to 32 bits
+        let value = if load_store.w_view { value as u32 as DWordType } else { value };
 
-        let rd = load_store.rd.phys_reg.unwrap();
+        // a load into the zero register is discarded
         load_store.rd.value = Some(value);
-        self.phys_reg_file.borrow_mut().set_value(rd, value);
+        if let Some(rd) = load_store.rd.phys_reg {
+            self.phys_reg_file.borrow_mut().set_value(rd, value);
+            self.broadcast_buffer.borrow_mut().push(CDBBroadcast { phys_reg: rd, value });
+        }
 
-        rob_slot.renamed_registers.push(load_store.rd.clone())
+        if let Some(rd2) = &mut load_store.rd2 {
+            rd2.value = Some(values[1]);
+            if let Some(phys_reg) = rd2.phys_reg {
+                self.phys_reg_file.borrow_mut().set_value(phys_reg, values[1]);
+                self.broadcast_buffer.borrow_mut().push(CDBBroadcast { phys_reg, value: values[1] });
+            }
+        }
     }
 
     fn execute_branch(&mut self, branch: &mut RSBranch, rob_slot: &mut ROBSlot) {
         let branch_target = match &branch.opcode {
             Opcode::B => self.execute_B(branch, rob_slot),
             Opcode::BL => self.execute_BL(branch, rob_slot),
-            Opcode::BX => self.execute_BX(branch, rob_slot),
-            Opcode::BEQ => self.execute_BEQ(branch, rob_slot),
-            Opcode::BNE => self.execute_BNE(branch, rob_slot),
-            Opcode::BGT => self.execute_BGT(branch, rob_slot),
-            Opcode::BGE => self.execute_BGE(branch, rob_slot),
-            Opcode::BLT => self.execute_BLT(branch, rob_slot),
-            Opcode::BLE => self.execute_BLE(branch, rob_slot),
+            Opcode::BX |
+            Opcode::BR => self.execute_BX(branch, rob_slot),
+            Opcode::BLR => self.execute_BL(branch, rob_slot),
+            Opcode::BCOND => self.execute_BCOND(branch, rob_slot),
             Opcode::CBZ => self.execute_CBZ(branch, rob_slot),
             Opcode::CBNZ => self.execute_CBNZ(branch, rob_slot),
+            Opcode::TBZ |
+            Opcode::TBNZ => self.execute_test_bit(branch, rob_slot),
             Opcode::RET => self.execute_RET(branch, rob_slot),
             _ => unreachable!()
         };
@@ -373,88 +816,26 @@ impl EU {
         branch.target.value() as usize
     }
 
-    fn execute_BEQ(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
-        let target = branch.target.value() as u64;
-        let cpsr = branch.rt.as_ref().unwrap().value.unwrap();
-        let pc = rob_slot.pc as DWordType;
-
-        let zero_flag = (cpsr >> ZERO_FLAG) & 0x1;
-        if zero_flag == 1 {
-            target as usize
-        } else {
-            (pc + 1) as usize
-        }
-    }
-
-    fn execute_BNE(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
-        let target = branch.target.value() as u64;
-        let cpsr = branch.rt.as_ref().unwrap().value.unwrap();
-        let pc = rob_slot.pc as DWordType;
-
-        let zero_flag = (cpsr >> ZERO_FLAG) & 0x1;
-        if zero_flag == 0 {
-            target as usize
-        } else {
-            (pc + 1) as usize
-        }
-    }
-
-    fn execute_BLT(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
-        let target = branch.target.value() as u64;
-        let cpsr = branch.rt.as_ref().unwrap().value.unwrap();
-        let pc = rob_slot.pc as DWordType;
-
-        let negative_flag = (cpsr >> NEGATIVE_FLAG) & 0x1;
-        let overflow_flag = (cpsr >> OVERFLOW_FLAG) & 0x1;
-
-        if negative_flag != overflow_flag {
-            target as usize
-        } else {
-            (pc + 1) as usize
-        }
-    }
-
-    fn execute_BLE(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
-        let target = branch.target.value() as u64;
-        let cpsr = branch.rt.as_ref().unwrap().value.unwrap();
-        let pc = rob_slot.pc as DWordType;
-
-        let zero_flag = (cpsr >> ZERO_FLAG) & 0x1;
-        let negative_flag = (cpsr >> NEGATIVE_FLAG) & 0x1;
-        let overflow_flag = (cpsr >> OVERFLOW_FLAG) & 0x1;
-
-        if (zero_flag == 1) || (negative_flag != overflow_flag) {
-            target as usize
-        } else {
-            (pc + 1) as usize
-        }
-    }
-
-    fn execute_BGT(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
+    fn execute_BCOND(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
         let target = branch.target.value() as u64;
         let cpsr = branch.rt.as_ref().unwrap().value.unwrap();
         let pc = rob_slot.pc as DWordType;
 
-        let zero_flag = (cpsr >> ZERO_FLAG) & 0x1;
-        let negative_flag = (cpsr >> NEGATIVE_FLAG) & 0x1;
-        let overflow_flag = (cpsr >> OVERFLOW_FLAG) & 0x1;
-
-        if (zero_flag == 0) && (negative_flag == overflow_flag) {
+        if condition_holds(branch.condition, cpsr) {
             target as usize
         } else {
             (pc + 1) as usize
         }
     }
 
-    fn execute_BGE(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
+    // TBZ branches if the bit is zero and TBNZ if the bit is one.
+    fn execute_test_bit(&mut self, branch: &RSBranch, rob_slot: &mut ROBSlot) -> usize {
+        let reg_value = branch.rt.as_ref().unwrap().value.unwrap();
         let target = branch.target.value() as u64;
-        let cpsr = branch.rt.as_ref().unwrap().value.unwrap();
         let pc = rob_slot.pc as DWordType;
 
-        let negative_flag = (cpsr >> NEGATIVE_FLAG) & 0x1;
-        let overflow_flag = (cpsr >> OVERFLOW_FLAG) & 0x1;
-
-        if negative_flag == overflow_flag {
+        let bit_set = (reg_value >> branch.bit) & 0x1 == 1;
+        if bit_set == (branch.opcode == Opcode::TBNZ) {
             target as usize
         } else {
             (pc + 1) as usize
@@ -505,16 +886,39 @@ impl EU {
         branch_target as usize
     }
 
-    fn execute_ADR(&mut self, _rs: &mut RS, _rob_slot: &mut ROBSlot) {
-        panic!("ADR is not implemented");
+    // The operand2 is the offset relative to the PC; for ADRP relative to the 4KB page of the PC.
+    fn execute_ADR(&mut self, data_processing: &mut RSDataProcessing, rob_slot: &mut ROBSlot) -> DWordType {
+        let pc = rob_slot.pc as DWordType;
+        let base = if data_processing.opcode == Opcode::ADRP { pc & !0xFFF } else { pc };
+        base.wrapping_add(data_processing.operand2_value())
     }
 }
 
-/// The table containing all execution units of a CPU core.
+/// An execution port. Every cycle a port can accept a single instruction for one of its units.
+struct Port {
+    name: String,
+    units: Vec<UnitType>,
+    // set when an instruction was dispatched to the port in the current cycle.
+    dispatched: bool,
+}
+
+pub(crate) enum PortLookup {
+    // the port the instruction can be dispatched to.
+    Found(u8),
+    // the compatible ports are blocked by an older instruction on a unit that isn't pipelined.
+    Busy,
+    // the compatible ports have already accepted an instruction this cycle.
+    Conflict,
+}
+
+/// The table containing all execution units of a CPU core. An execution unit holds a single
+/// instruction that is being executed; the ports determine which instructions can be
+/// dispatched to the execution units in a cycle.
 pub(crate) struct EUTable {
     pub(crate) capacity: u8,
     idle_stack: Vec<u8>,
     array: Vec<EU>,
+    ports: Vec<Port>,
 }
 
 impl EUTable {
@@ -532,9 +936,12 @@ impl EUTable {
             array.push(EU {
                 index: i,
                 cycles_remaining: 0,
+                unit_type: None,
+                port: None,
                 rs_index: None,
                 state: EUState::IDLE,
                 trace: cpu_config.trace.execute,
+                memory_disambiguation: cpu_config.memory_disambiguation,
                 memory_subsystem: Rc::clone(memory_subsystem),
                 perf_counters: Rc::clone(perf_counters),
                 phys_reg_file: Rc::clone(phys_reg_file),
@@ -543,37 +950,85 @@ impl EUTable {
             free_stack.push(i);
         }
 
+        let mut ports = Vec::with_capacity(cpu_config.ports.len());
+        for port_config in &cpu_config.ports {
+            ports.push(Port {
+                name: port_config.name.clone(),
+                units: port_config.units.clone(),
+                dispatched: false,
+            });
+        }
+
+        for unit_type in [UnitType::Alu, UnitType::Mul, UnitType::Div, UnitType::Load, UnitType::Store, UnitType::Branch] {
+            assert!(ports.iter().any(|port| port.units.contains(&unit_type)),
+                    "EUTable: there is no port for unit type {:?}", unit_type);
+        }
+
         EUTable {
             capacity,
             array,
             idle_stack: free_stack,
+            ports,
         }
     }
 
-    pub(crate) fn flush(&mut self) {
-        self.idle_stack.clear();
-        for k in 0..self.capacity {
-            self.idle_stack.push(k);
-            self.array.get_mut(k as usize).unwrap().reset();
+    // Needs to be called at the beginning of every cycle before instructions are dispatched.
+    pub(crate) fn reset_ports(&mut self) {
+        for port in &mut self.ports {
+            port.dispatched = false;
         }
     }
 
+    pub(crate) fn port_names(&self) -> Vec<String> {
+        self.ports.iter().map(|port| port.name.clone()).collect()
+    }
+
+    // Finds a port that can accept an instruction for the given unit type in this cycle.
+    pub(crate) fn find_port(&self, unit_type: UnitType, pipelined: bool) -> PortLookup {
+        let mut result = PortLookup::Conflict;
+        for (port_index, port) in self.ports.iter().enumerate() {
+            if !port.units.contains(&unit_type) {
+                continue;
+            }
+
+            if !pipelined && self.is_busy(port_index as u8, unit_type) {
+                result = PortLookup::Busy;
+                continue;
+            }
+
+            if !port.dispatched {
+                return PortLookup::Found(port_index as u8);
+            }
+        }
+        result
+    }
+
     pub(crate) fn has_idle(&self) -> bool {
         return !self.idle_stack.is_empty();
     }
 
+    // Checks if the unit of the given type on the port is executing an instruction.
+    fn is_busy(&self, port: u8, unit_type: UnitType) -> bool {
+        self.array.iter().any(|eu| eu.state == EUState::EXECUTING && eu.port == Some(port) && eu.unit_type == Some(unit_type))
+    }
+
     pub(crate) fn get_mut(&mut self, eu_index: u8) -> &mut EU {
         self.array.get_mut(eu_index as usize).unwrap()
     }
 
-    pub(crate) fn allocate(&mut self) -> u8 {
+    pub(crate) fn allocate(&mut self, port: u8) -> u8 {
         if let Some(last_element) = self.idle_stack.pop() {
+            let port_ref = &mut self.ports[port as usize];
+            debug_assert!(!port_ref.dispatched);
+            port_ref.dispatched = true;
+
             let eu = self.array.get_mut(last_element as usize).unwrap();
             debug_assert!(eu.state == EUState::IDLE);
             debug_assert!(eu.rs_index.is_none());
             debug_assert!(eu.cycles_remaining == 0);
 
             eu.state = EUState::EXECUTING;
+            eu.port = Some(port);
             return last_element;
         } else {
             panic!("No free PhysReg")
@@ -582,7 +1037,7 @@ impl EUTable {
 
     pub(crate) fn deallocate(&mut self, eu_index: u8) {
         let eu = self.array.get_mut(eu_index as usize).unwrap();
-        debug_assert!(eu.state == EUState::EXECUTING || eu.state == EUState::COMPLETED);
+        debug_assert!(eu.state == EUState::EXECUTING || eu.state == EUState::COMPLETED || eu.state == EUState::REPLAY);
         debug_assert!(eu.rs_index.is_some());
         debug_assert!(!self.idle_stack.contains(&eu_index));
 
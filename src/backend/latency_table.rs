@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::cpu::{CPUConfig, UnitType};
+use crate::instructions::instructions::{Opcode, unit_type};
+
+// The default latency in cycles; loosely based on a Cortex-A72.
+fn default_latency(opcode: Opcode) -> u8 {
+    match opcode {
+        Opcode::MUL |
+        Opcode::MADD |
+        Opcode::MSUB |
+        Opcode::MNEG |
+        Opcode::SMULL |
+        Opcode::UMULL => 3,
+        // the high half of the 128 bits product
+        Opcode::SMULH |
+        Opcode::UMULH => 5,
+        Opcode::SDIV |
+        Opcode::UDIV => 12,
+        // L1 hit
+        Opcode::LDR |
+        Opcode::LDRB |
+        Opcode::LDRH |
+        Opcode::LDRSB |
+        Opcode::LDRSH |
+        Opcode::LDRSW |
+        Opcode::LDP |
+        Opcode::LDAR |
+        Opcode::LDAPR => 4,
+        _ => 1,
+    }
+}
+
+fn default_pipelined(unit_type: UnitType) -> bool {
+    // the divider blocks until the division has completed
+    !matches!(unit_type, UnitType::Div)
+}
+
+/// Contains the execution latency per opcode and if the unit types are pipelined.
+pub(crate) struct LatencyTable {
+    latencies: HashMap<Opcode, u8>,
+    pipelined: HashMap<UnitType, bool>,
+}
+
+impl LatencyTable {
+    pub(crate) fn new(cpu_config: &CPUConfig) -> LatencyTable {
+        for (opcode, latency) in &cpu_config.latencies {
+            assert!(*latency > 0, "LatencyTable: the latency of {:?} must be larger than 0", opcode);
+        }
+
+        LatencyTable {
+            latencies: cpu_config.latencies.clone(),
+            pipelined: cpu_config.pipelined.clone(),
+        }
+    }
+
+    pub(crate) fn latency(&self, opcode: Opcode) -> u8 {
+        match self.latencies.get(&opcode) {
+            Some(latency) => *latency,
+            None => default_latency(opcode),
+        }
+    }
+
+    // A pipelined unit can accept a new instruction every cycle. A unit that isn't pipelined
+    // can only execute a single instruction at a time.
+    pub(crate) fn is_pipelined(&self, opcode: Opcode) -> bool {
+        let unit_type = unit_type(opcode);
+        match self.pipelined.get(&unit_type) {
+            Some(pipelined) => *pipelined,
+            None => default_pipelined(unit_type),
+        }
+    }
+}
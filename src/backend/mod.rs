@@ -3,4 +3,5 @@ mod reservation_station;
 mod reorder_buffer;
 mod physical_register;
 mod register_alias_table;
-mod execution_unit;
\ No newline at end of file
+mod execution_unit;
+mod latency_table;
\ No newline at end of file
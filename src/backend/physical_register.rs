@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 use crate::instructions::instructions::{DWordType, RegisterType};
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -22,8 +24,10 @@ impl PhysRegEntry {
 
 pub(crate) struct PhysRegFile {
     free_stack: Vec<u16>,
-    count: u16,
     entries: Vec<PhysRegEntry>,
+    // the copies of the free stack taken at every in flight branch; ordered by the rob sequence
+    // of the branch.
+    checkpoints: VecDeque<(u64, Vec<u16>)>,
 }
 
 impl PhysRegFile {
@@ -39,7 +43,7 @@ impl PhysRegFile {
             free_stack.push(count - 1 - i);
         }
 
-        PhysRegFile { count, entries, free_stack }
+        PhysRegFile { entries, free_stack, checkpoints: VecDeque::new() }
     }
 
     pub(crate) fn get(&self, reg: RegisterType) -> &PhysRegEntry {
@@ -79,18 +83,63 @@ impl PhysRegFile {
         }
     }
 
-    pub(crate) fn flush(&mut self) {
-        self.free_stack.clear();
+    // Takes a checkpoint of the free stack for the branch with the given rob sequence.
+    pub(crate) fn checkpoint(&mut self, rob_seq: u64) {
+        debug_assert!(self.checkpoints.back().is_none_or(|(seq, _)| *seq < rob_seq));
+
+        self.checkpoints.push_back((rob_seq, self.free_stack.clone()));
+    }
+
+    // Restores the free stack from the checkpoint of the branch with the given rob sequence. Every
+    // register allocated after the checkpoint is released; the checkpoints of the younger branches
+    // are discarded.
+    pub(crate) fn restore(&mut self, rob_seq: u64) {
+        self.flush(rob_seq + 1);
+
+        let (seq, free_stack) = self.checkpoints.back().expect("PhysRegFile: no checkpoint found");
+        debug_assert!(*seq == rob_seq, "PhysRegFile: no checkpoint for rob_seq {}", rob_seq);
+        self.free_stack = free_stack.clone();
+
+        for reg in &self.free_stack {
+            let entry = &mut self.entries[*reg as usize];
+            if entry.state == PhysRegEntryState::BUSY {
+                entry.reset();
+            }
+        }
+    }
 
-        for i in 0..self.count {
-            let entry = &mut self.entries[i as usize];
+    // Discards the checkpoint of the branch with the given rob sequence; called when the branch retires.
+    pub(crate) fn release(&mut self, rob_seq: u64) {
+        if let Some((seq, _)) = self.checkpoints.front() {
+            if *seq == rob_seq {
+                self.checkpoints.pop_front();
+            }
+        }
+    }
 
-            self.free_stack.push(i);
-            entry.reset();
+    // Discards the checkpoints of the branches with a rob sequence equal or larger than the given one.
+    // The registers of the flushed instructions should be returned using squash.
+    pub(crate) fn flush(&mut self, rob_seq: u64) {
+        while let Some((seq, _)) = self.checkpoints.back() {
+            if *seq < rob_seq {
+                break;
+            }
+            self.checkpoints.pop_back();
         }
     }
 
+    // Deallocates a register of a retired instruction. The register was allocated before any of
+    // the checkpoints was taken, so it is also added to the free stack of every checkpoint.
     pub(crate) fn deallocate(&mut self, reg: RegisterType) {
+        self.squash(reg);
+
+        for (_, free_stack) in &mut self.checkpoints {
+            free_stack.push(reg);
+        }
+    }
+
+    // Deallocates a register of an instruction that was flushed.
+    pub(crate) fn squash(&mut self, reg: RegisterType) {
         debug_assert!(!self.free_stack.contains(&reg), "Phys register {} can't be deallocated while it is also on the free stack", reg);
 
         let entry = self.get_mut(reg);
@@ -118,4 +167,21 @@ mod tests {
         assert_eq!(entry.has_value, false);
         assert_eq!(entry.value, 0);
     }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let mut reg_file = PhysRegFile::new(8);
+        let older = reg_file.allocate();
+        reg_file.checkpoint(10);
+        let younger = reg_file.allocate();
+        reg_file.set_value(younger, 5);
+
+        // the older register retires after the checkpoint was taken
+        reg_file.deallocate(older);
+        reg_file.restore(10);
+
+        assert_eq!(reg_file.free_stack.len(), 8);
+        assert_eq!(reg_file.entries[younger as usize].state, PhysRegEntryState::IDLE);
+        assert_eq!(reg_file.entries[older as usize].state, PhysRegEntryState::IDLE);
+    }
 }
@@ -1,5 +1,8 @@
+use std::collections::VecDeque;
+
 use crate::instructions::instructions::RegisterType;
 
+#[derive(Clone)]
 pub(crate) struct RATEntry {
     // the physical register in the arch register to phys register mapping
     pub(crate) phys_reg: RegisterType,
@@ -13,22 +16,85 @@ pub(crate) struct RATEntry {
 /// exists, it should be used.
 pub(crate) struct RAT {
     pub(crate) table: Vec<RATEntry>,
+    // the copies of the table taken at every in flight branch; ordered by the rob sequence
+    // of the branch. When a branch is mispredicted, the table is restored from its checkpoint.
+    checkpoints: VecDeque<(u64, Vec<RATEntry>)>,
 }
 
 impl RAT {
-    pub(crate) fn new(phys_reg_count: u16) -> Self {
-        let mut table = Vec::with_capacity(phys_reg_count as usize);
-        for _ in 0..phys_reg_count {
+    pub(crate) fn new(arch_reg_count: u16) -> Self {
+        let mut table = Vec::with_capacity(arch_reg_count as usize);
+        for _ in 0..arch_reg_count {
             table.push(RATEntry { phys_reg: 0, valid: false });
         }
-        Self { table }
+        Self { table, checkpoints: VecDeque::new() }
     }
 
-    pub(crate) fn flush(&mut self) {
+    // Invalidates all mappings and discards the checkpoints of the branches with a rob sequence
+    // equal or larger than the given one. The mappings of the remaining instructions should be
+    // restored by walking the rob.
+    pub(crate) fn flush(&mut self, rob_seq: u64) {
         for k in 0..self.table.len() {
             let option = self.table.get_mut(k).unwrap();
             option.valid = false;
         }
+
+        while let Some((seq, _)) = self.checkpoints.back() {
+            if *seq < rob_seq {
+                break;
+            }
+            self.checkpoints.pop_back();
+        }
+    }
+
+    // Takes a checkpoint for the branch with the given rob sequence.
+    pub(crate) fn checkpoint(&mut self, rob_seq: u64) {
+        debug_assert!(self.checkpoints.back().is_none_or(|(seq, _)| *seq < rob_seq));
+
+        self.checkpoints.push_back((rob_seq, self.table.clone()));
+    }
+
+    // Restores the table from the checkpoint of the branch with the given rob sequence. The
+    // checkpoints of the younger branches are discarded.
+    pub(crate) fn restore(&mut self, rob_seq: u64) {
+        while let Some((seq, _)) = self.checkpoints.back() {
+            if *seq <= rob_seq {
+                break;
+            }
+            self.checkpoints.pop_back();
+        }
+
+        let (seq, table) = self.checkpoints.back().expect("RAT: no checkpoint found");
+        debug_assert!(*seq == rob_seq, "RAT: no checkpoint for rob_seq {}", rob_seq);
+        self.table = table.clone();
+    }
+
+    // Discards the checkpoint of the branch with the given rob sequence; called when the branch retires.
+    pub(crate) fn release(&mut self, rob_seq: u64) {
+        if let Some((seq, _)) = self.checkpoints.front() {
+            if *seq == rob_seq {
+                self.checkpoints.pop_front();
+            }
+        }
+    }
+
+    // Called when the instruction that renamed arch_reg to phys_reg retires. If the arch_reg still
+    // maps to the phys_reg, the mapping is invalidated because the value will be found in the
+    // architectural register file. The same is done for the checkpoints because the phys_reg is
+    // going to be deallocated.
+    pub(crate) fn retire(&mut self, arch_reg: RegisterType, phys_reg: RegisterType) {
+        let rat_entry = self.get_mut(arch_reg);
+        debug_assert!(rat_entry.valid);
+        if rat_entry.phys_reg == phys_reg {
+            rat_entry.valid = false;
+        }
+
+        for (_, table) in &mut self.checkpoints {
+            let rat_entry = &mut table[arch_reg as usize];
+            if rat_entry.valid && rat_entry.phys_reg == phys_reg {
+                rat_entry.valid = false;
+            }
+        }
     }
 
     pub(crate) fn update(&mut self, reg_a: RegisterType, reg_p: RegisterType) {
@@ -1,7 +1,7 @@
 use std::rc::Rc;
 
 use crate::backend::reservation_station::RenamedRegister;
-use crate::instructions::instructions::Instr;
+use crate::instructions::instructions::{DWordType, Instr};
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub(crate) enum ROBSlotState {
@@ -28,7 +28,18 @@ pub(crate) struct ROBSlot {
     pub(crate) branch_target_predicted: usize,
     pub(crate) branch_target_actual: usize,
     pub(crate) sb_pos: Option<u16>,
+    // the store buffer slot of the second register of a store pair
+    pub(crate) sb_pos2: Option<u16>,
+    pub(crate) lq_pos: Option<u16>,
     pub(crate) eu_index: Option<u8>,
+    // the address of a misaligned load or store. The fault is raised when the instruction retires
+    // because it could be on a mispredicted path.
+    pub(crate) alignment_fault: Option<DWordType>,
+    // the address of a load or store that falls outside of the memory. Like the alignment fault
+    // it is raised when the instruction retires.
+    pub(crate) out_of_range_fault: Option<DWordType>,
+    // the instruction was fetched from a pc outside of the program; it is a NOP that faults when it retires.
+    pub(crate) fetch_fault: bool,
 }
 
 impl ROBSlot {
@@ -39,7 +50,12 @@ impl ROBSlot {
         self.rs_index = None;
         self.instr = None;
         self.sb_pos = None;
+        self.sb_pos2 = None;
+        self.lq_pos = None;
         self.eu_index = None;
+        self.alignment_fault = None;
+        self.out_of_range_fault = None;
+        self.fetch_fault = false;
         self.pc = 0;
         self.renamed_registers.clear();
     }
@@ -48,7 +64,6 @@ impl ROBSlot {
 pub(crate) struct ROB {
     pub(crate) capacity: u16,
     pub(crate) seq_issued: u64,
-    pub(crate) seq_dispatched: u64,
     pub(crate) seq_rs_allocated: u64,
     pub(crate) seq_retired: u64,
     pub(crate) head: u64,
@@ -69,7 +84,12 @@ impl ROB {
                 branch_target_predicted: 0,
                 branch_target_actual: 0,
                 sb_pos: None,
+                sb_pos2: None,
+                lq_pos: None,
                 eu_index: None,
+                alignment_fault: None,
+                out_of_range_fault: None,
+                fetch_fault: false,
                 pc: 0,
             });
         }
@@ -77,7 +97,6 @@ impl ROB {
         Self {
             capacity,
             seq_issued: 0,
-            seq_dispatched: 0,
             seq_rs_allocated: 0,
             seq_retired: 0,
             tail: 0,
@@ -104,6 +123,13 @@ impl ROB {
         (seq % self.capacity as u64) as u16
     }
 
+    // The sequence of the slot at the given index.
+    pub(crate) fn to_seq(&self, slot_index: u16) -> u64 {
+        let head_index = self.to_index(self.head) as u64;
+        let capacity = self.capacity as u64;
+        self.head + (slot_index as u64 + capacity - head_index) % capacity
+    }
+
     pub(crate) fn deallocate(&mut self) {
         debug_assert!(!self.is_empty(), "ROB: Can't deallocate if ROB is empty");
 
@@ -124,17 +150,18 @@ impl ROB {
         return self.capacity > self.size();
     }
 
-    pub(crate) fn flush(&mut self) {
-        // todo: we don't need to go over the whole rob; just over the busy slots
-        for i in 0..self.capacity {
-            let slot = &mut self.slots[i as usize];
-            slot.reset();
+    // Removes all slots from the given sequence up to the tail. The resources of the slots
+    // should already be released.
+    pub(crate) fn flush(&mut self, seq: u64) {
+        debug_assert!(seq >= self.head && seq <= self.tail);
+
+        for k in seq..self.tail {
+            let index = self.to_index(k) as usize;
+            self.slots[index].reset();
         }
-        self.head = 0;
-        self.tail = 0;
-        self.seq_retired = 0;
-        self.seq_issued = 0;
-        self.seq_rs_allocated = 0;
-        self.seq_dispatched = 0;
+
+        self.tail = seq;
+        self.seq_issued = seq;
+        self.seq_rs_allocated = self.seq_rs_allocated.min(seq);
     }
 }
@@ -1,6 +1,6 @@
 use std::collections::{HashSet, VecDeque};
 
-use crate::instructions::instructions::{ConditionCode, DWordType, Opcode, RegisterType};
+use crate::instructions::instructions::{ConditionCode, DataType, DWordType, ExtendType, Opcode, RegisterType, ShiftType};
 use crate::instructions::instructions::Opcode::NOP;
 
 #[derive(Clone)]
@@ -20,9 +20,27 @@ pub enum RSOperand2 {
     Immediate {
         value: DWordType,
     },
+    ShiftedImmediate {
+        value: DWordType,
+        shift: u8,
+    },
+    Bitfield {
+        lsb: u8,
+        width: u8,
+    },
     Register {
         register: RenamedRegister,
     },
+    ShiftedRegister {
+        register: RenamedRegister,
+        shift_type: ShiftType,
+        amount: u8,
+    },
+    ExtendedRegister {
+        register: RenamedRegister,
+        extend_type: ExtendType,
+        amount: u8,
+    },
     Unused(),
 }
 
@@ -30,12 +48,34 @@ impl RSOperand2 {
     pub fn value(&self) -> DWordType {
         match self {
             RSOperand2::Immediate { value } => *value,
+            RSOperand2::ShiftedImmediate { value, shift } => *value << shift,
             RSOperand2::Register { register } => register.value.unwrap(),
+            RSOperand2::ShiftedRegister { register, shift_type, amount } =>
+                shift_type.apply(register.value.unwrap(), *amount),
+            RSOperand2::ExtendedRegister { register, extend_type, amount } =>
+                extend_type.apply(register.value.unwrap(), *amount),
+            RSOperand2::Bitfield { .. } |
             RSOperand2::Unused() => panic!(),
         }
     }
 }
 
+pub enum RSAddressingMode {
+    Offset {
+        offset: i64,
+    },
+    PreIndex {
+        offset: i64,
+    },
+    PostIndex {
+        offset: i64,
+    },
+    RegisterOffset {
+        rm: RenamedRegister,
+        shift: u8,
+    },
+}
+
 pub struct RSDataProcessing {
     pub opcode: Opcode,
     pub condition: ConditionCode,
@@ -43,9 +83,28 @@ pub struct RSDataProcessing {
     pub rd: RenamedRegister,
     // the original value of the rd register (needed for condition codes)
     pub rd_src: Option<RenamedRegister>,
-    // the cpsr for condition codes
+    // the cpsr for condition codes and the carry flag
     pub cpsr: Option<RenamedRegister>,
     pub operand2: RSOperand2,
+    // the cpsr written by the flag setting instructions
+    pub cpsr_dst: Option<RenamedRegister>,
+    // the flags of a conditional compare when the condition doesn't hold
+    pub nzcv: u8,
+    // the accumulator of the multiply-accumulate instructions
+    pub ra: Option<RenamedRegister>,
+    // the 32 bits variant that operates on the W views of the registers
+    pub w_view: bool,
+}
+
+impl RSDataProcessing {
+    // The value of operand2; the 32 bits variant shifts within the lower 32 bits.
+    pub fn operand2_value(&self) -> DWordType {
+        match &self.operand2 {
+            RSOperand2::ShiftedRegister { register, shift_type, amount } if self.w_view =>
+                shift_type.apply_w(register.value.unwrap(), *amount),
+            operand2 => operand2.value(),
+        }
+    }
 }
 
 pub enum RSBranchTarget {
@@ -72,6 +131,7 @@ pub struct RSBranch {
     pub lr: Option<RenamedRegister>,
     pub target: RSBranchTarget,
     pub rt: Option<RenamedRegister>,
+    pub bit: u8,
 }
 
 pub struct RSLoadStore {
@@ -79,7 +139,14 @@ pub struct RSLoadStore {
     pub condition: ConditionCode,
     pub rn: RenamedRegister,
     pub rd: RenamedRegister,
-    pub offset: u16,
+    // the second register of a load or store pair
+    pub rd2: Option<RenamedRegister>,
+    pub addressing_mode: RSAddressingMode,
+    // the base register after the writeback of the pre and post index addressing modes
+    pub rn_wb: Option<RenamedRegister>,
+    pub data_type: DataType,
+    pub signed: bool,
+    pub w_view: bool,
 }
 
 pub struct RSPrintr {
@@ -186,6 +253,19 @@ impl RSTable {
         self.ready_queue.push_front(rs_index);
     }
 
+    // Enqueues a reservation station that needs to be dispatched again. It is placed at the
+    // end of the ready queue so it doesn't starve the instructions it is waiting for.
+    pub(crate) fn enqueue_replay(&mut self, rs_index: u16) {
+        debug_assert!(!self.ready_queue.contains(&rs_index), "Can't enqueue replay rs_index={}, it is already on the ready queue", rs_index);
+        debug_assert!(self.allocated.contains(&rs_index), "Can't enqueue replay rs_index={}, it isn't in the allocated set", rs_index);
+
+        self.ready_queue.push_back(rs_index);
+    }
+
+    pub(crate) fn ready_cnt(&self) -> usize {
+        self.ready_queue.len()
+    }
+
     // todo: has_ready/dequeue_ready can be simplified by using an Option
     pub(crate) fn has_ready(&self) -> bool {
         !self.ready_queue.is_empty()
@@ -193,17 +273,6 @@ impl RSTable {
         //return self.ready_queue_head != self.ready_queue_tail;
     }
 
-    pub(crate) fn flush(&mut self) {
-        self.ready_queue.clear();
-        self.idle_stack.clear();
-        self.allocated.clear();
-
-        for k in 0..self.capacity {
-            self.array.get_mut(k as usize).unwrap().reset();
-            self.idle_stack.push(k);
-        }
-    }
-
     pub(crate) fn deque_ready(&mut self) -> u16 {
         debug_assert!(self.has_ready(), "RSTable: can't dequeue ready when there are no ready items");
         //let index = self.to_index(self.ready_queue_head);
@@ -246,6 +315,12 @@ impl RSTable {
         }
     }
 
+    // Deallocates a reservation station of an instruction that was on a mispredicted path.
+    pub(crate) fn squash(&mut self, rs_index: u16) {
+        self.ready_queue.retain(|index| *index != rs_index);
+        self.deallocate(rs_index);
+    }
+
     pub(crate) fn deallocate(&mut self, rs_index: u16) {
         let rs = &mut self.array[rs_index as usize];
 
@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::ops::Add;
@@ -9,8 +10,11 @@ use std::time::{Duration, Instant};
 use serde::Deserialize;
 
 use crate::backend::backend::Backend;
+use crate::frontend::branch_predictor::create_branch_predictor;
+use crate::frontend::branch_target_buffer::BTB;
 use crate::frontend::frontend::{Frontend, FrontendControl};
-use crate::instructions::instructions::{DWordType, InstrQueue, Program, RegisterType};
+use crate::frontend::return_address_stack::ReturnAddressStack;
+use crate::instructions::instructions::{DWordType, InstrQueue, Opcode, Program, RegisterType};
 use crate::memory_subsystem::memory_subsystem::MemorySubsystem;
 
 pub struct PerfCounters {
@@ -23,11 +27,45 @@ pub struct PerfCounters {
     pub retired_cnt: u64,
     pub bad_speculation_cnt: u64,
     pub pipeline_flushes: u64,
+    // the number of times the instructions after a mispredicted branch were squashed
+    pub branch_squash_cnt: u64,
+    // the number of rob slots walked to rebuild the RAT after a flush
+    pub rob_walk_cnt: u64,
     pub cycle_cnt: u64,
+    // the number of loads that got their value forwarded from the store buffer
+    pub load_forwarded_cnt: u64,
+    // the number of times a load was replayed because an older store had an unknown address
+    pub load_blocked_cnt: u64,
+    // the number of pipeline flushes caused by a load that executed before an older store to the same address
+    pub memory_order_violation_cnt: u64,
+    // the number of times a load was replayed because an older acquire load hasn't executed yet
+    pub acquire_blocked_cnt: u64,
+    // the number of times an LDAR was replayed because an older release store wasn't written to memory yet
+    pub release_blocked_cnt: u64,
+    // the number of conditional branches for which the direction was correctly predicted
+    pub branch_direction_good_cnt: u64,
+    // the number of conditional branches for which the direction was mispredicted
+    pub branch_direction_miss_cnt: u64,
+    // the number of returns for which the return address stack predicted the correct target
+    pub ras_hit_cnt: u64,
+    // the number of returns for which the return address stack predicted the wrong target
+    pub ras_miss_cnt: u64,
+    // the number of calls that overwrote the oldest entry of the return address stack
+    pub ras_overflow_cnt: u64,
+    // the number of indirect branches for which the branch target buffer predicted the correct target
+    pub btb_hit_cnt: u64,
+    // the number of indirect branches for which the branch target buffer predicted the wrong target
+    pub btb_miss_cnt: u64,
+    // the number of times an instruction couldn't be dispatched because its unit isn't pipelined and is busy
+    pub unit_busy_cnt: u64,
+    // the number of times an instruction couldn't be dispatched because its ports already accepted an instruction
+    pub port_conflict_cnt: u64,
+    // the number of instructions dispatched per port
+    pub port_dispatch_cnt: Vec<u64>,
 }
 
 impl PerfCounters {
-    pub fn new() -> Self {
+    pub fn new(port_count: usize) -> Self {
         Self {
             decode_cnt: 0,
             issue_cnt: 0,
@@ -39,6 +77,23 @@ impl PerfCounters {
             branch_miss_prediction_cnt: 0,
             branch_good_predictions_cnt: 0,
             pipeline_flushes: 0,
+            branch_squash_cnt: 0,
+            rob_walk_cnt: 0,
+            load_forwarded_cnt: 0,
+            load_blocked_cnt: 0,
+            memory_order_violation_cnt: 0,
+            acquire_blocked_cnt: 0,
+            release_blocked_cnt: 0,
+            branch_direction_good_cnt: 0,
+            branch_direction_miss_cnt: 0,
+            ras_hit_cnt: 0,
+            ras_miss_cnt: 0,
+            ras_overflow_cnt: 0,
+            btb_hit_cnt: 0,
+            btb_miss_cnt: 0,
+            unit_busy_cnt: 0,
+            port_conflict_cnt: 0,
+            port_dispatch_cnt: vec![0; port_count],
         }
     }
 }
@@ -68,7 +123,94 @@ impl Default for Trace {
     }
 }
 
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BranchPredictorType {
+    // backwards branches are predicted taken, forward branches not taken.
+    Static,
+    // a table of 2-bit counters indexed by the pc.
+    Bimodal,
+    // a table of 2-bit counters indexed by the pc xor-ed with the global history.
+    Gshare,
+    // tagged tables indexed with geometrically increasing history lengths.
+    Tage,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+#[serde(default)]
+pub struct BranchPredictorConfig {
+    // the type of predictor for the direction of conditional branches
+    pub kind: BranchPredictorType,
+    // the number of entries per prediction table
+    pub table_size: u16,
+    // the number of bits of global branch history (max 64)
+    pub history_bits: u8,
+}
+
+impl Default for BranchPredictorConfig {
+    fn default() -> Self {
+        BranchPredictorConfig {
+            kind: BranchPredictorType::Static,
+            table_size: 1024,
+            history_bits: 16,
+        }
+    }
+}
+
+// How loads deal with older stores that haven't resolved their address yet.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryDisambiguation {
+    // loads wait until all older stores have resolved their address.
+    Conservative,
+    // loads speculate that older stores with an unknown address don't alias. When the
+    // speculation turns out to be wrong, the pipeline is flushed from the offending load.
+    Aggressive,
+}
+
+// How the RAT and the free list of the physical registers are recovered after a flush.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RenameRecovery {
+    // a copy of the RAT and the free list is taken at every branch and restored when the
+    // branch was mispredicted.
+    Checkpoint,
+    // the RAT is rebuilt by walking the rob from the head up to the flushed instruction and the
+    // physical registers of the flushed instructions are returned one by one. The walk stalls
+    // the renaming for a number of cycles.
+    RobWalk,
+}
+
+// The type of execution unit an instruction needs.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitType {
+    Alu,
+    Mul,
+    Div,
+    Load,
+    Store,
+    Branch,
+}
+
 #[derive(Clone, Deserialize, Debug)]
+pub struct PortConfig {
+    // the name of the port; only used for the stats
+    pub name: String,
+    // the types of units behind the port
+    pub units: Vec<UnitType>,
+}
+
+impl PortConfig {
+    fn new(name: &str, units: &[UnitType]) -> Self {
+        PortConfig { name: name.to_string(), units: units.to_vec() }
+    }
+}
+
+// Settings that are missing from the config file get their value from the Default impl; so
+// older config files keep working and new settings are opt-in.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(default)]
 pub struct CPUConfig {
     // the number of physical registers
     pub phys_reg_count: u16,
@@ -80,18 +222,26 @@ pub struct CPUConfig {
     pub frequency_hz: u64,
     // the number of reservation stations
     pub rs_count: u16,
-    // the size of the memory in machine words
+    // the size of the memory in bytes
     pub memory_size: u32,
     // the capacity of the store buffer
     pub sb_capacity: u16,
+    // the capacity of the load queue
+    pub lq_capacity: u16,
+    // how loads deal with older stores that have an unknown address
+    pub memory_disambiguation: MemoryDisambiguation,
+    // how the RAT and the free list of the physical registers are recovered after a flush
+    pub rename_recovery: RenameRecovery,
     // the number of line fill buffers; currently there are no line fill buffer
     // it is just a limit of the number of stores that can commit to memory
     // per clock cycle (there is also no cache)
     pub lfb_count: u8,
     // the capacity of the reorder buffer
     pub rob_capacity: u16,
-    // the number of execution units
+    // the number of execution units; so the maximum number of instructions being executed at the same time
     pub eu_count: u8,
+    // the execution ports; every cycle a port can accept one instruction for one of its units
+    pub ports: Vec<PortConfig>,
     // if processing of a single instruction should be traced (printed)
     pub trace: Trace,
     // the number of instructions that can retire per clock cycle
@@ -102,6 +252,18 @@ pub struct CPUConfig {
     pub issue_n_wide: u8,
     // The delay between writing the CPU stats. A value of 0 means that stat writing is disabled.
     pub stats_seconds: u32,
+    // the predictor for conditional branches
+    pub branch_predictor: BranchPredictorConfig,
+    // the number of entries in the return address stack
+    pub ras_depth: u16,
+    // the number of entries in the branch target buffer
+    pub btb_size: u16,
+    // the number of ways of each set of the branch target buffer
+    pub btb_associativity: u16,
+    // the execution latency in cycles per opcode; overrides the defaults
+    pub latencies: HashMap<Opcode, u8>,
+    // per unit type if it is pipelined; overrides the defaults
+    pub pipelined: HashMap<UnitType, bool>,
 }
 
 impl Default for CPUConfig {
@@ -112,16 +274,34 @@ impl Default for CPUConfig {
             instr_queue_capacity: 64,
             frequency_hz: 4,
             rs_count: 64,
-            memory_size: 128,
+            memory_size: 1024,
             sb_capacity: 16,
+            lq_capacity: 16,
+            memory_disambiguation: MemoryDisambiguation::Conservative,
+            rename_recovery: RenameRecovery::Checkpoint,
             lfb_count: 4,
             rob_capacity: 32,
             eu_count: 10,
+            ports: vec![
+                PortConfig::new("alu0", &[UnitType::Alu]),
+                PortConfig::new("alu1", &[UnitType::Alu]),
+                PortConfig::new("muldiv", &[UnitType::Mul, UnitType::Div]),
+                PortConfig::new("load0", &[UnitType::Load]),
+                PortConfig::new("load1", &[UnitType::Load]),
+                PortConfig::new("store", &[UnitType::Store]),
+                PortConfig::new("branch", &[UnitType::Branch]),
+            ],
             trace: Trace::default(),
             retire_n_wide: 4,
             dispatch_n_wide: 4,
             issue_n_wide: 4,
             stats_seconds: 0,
+            branch_predictor: BranchPredictorConfig::default(),
+            ras_depth: 16,
+            btb_size: 256,
+            btb_associativity: 4,
+            latencies: HashMap::new(),
+            pipelined: HashMap::new(),
         }
     }
 }
@@ -147,7 +327,7 @@ impl CPU {
     pub fn new(cpu_config: &CPUConfig) -> CPU {
         let instr_queue = Rc::new(RefCell::new(InstrQueue::new(cpu_config.instr_queue_capacity)));
 
-        let perf_counters = Rc::new(RefCell::new(PerfCounters::new()));
+        let perf_counters = Rc::new(RefCell::new(PerfCounters::new(cpu_config.ports.len())));
 
         let memory_subsystem = Rc::new(RefCell::new(
             MemorySubsystem::new(cpu_config)));
@@ -158,8 +338,16 @@ impl CPU {
         // on ARM the stack grows down (from larger address to smaller address)
         arch_reg_file.borrow_mut().set_value(SP, cpu_config.memory_size as DWordType);
 
+        let branch_predictor = Rc::new(RefCell::new(
+            create_branch_predictor(&cpu_config.branch_predictor)));
+
+        let return_address_stack = Rc::new(RefCell::new(
+            ReturnAddressStack::new(cpu_config.ras_depth)));
+
+        let btb = Rc::new(RefCell::new(BTB::new(cpu_config)));
+
         let frontend_control = Rc::new(RefCell::new(
-            FrontendControl { halted: false }));
+            FrontendControl { halted: false, exit: false, fetch_fault: false }));
 
         let backend = Backend::new(
             cpu_config,
@@ -168,6 +356,9 @@ impl CPU {
             &arch_reg_file,
             &frontend_control,
             &perf_counters,
+            &branch_predictor,
+            &return_address_stack,
+            &btb,
         );
 
         let frontend = Frontend::new(
@@ -176,6 +367,9 @@ impl CPU {
             &frontend_control,
             &perf_counters,
             &arch_reg_file,
+            &branch_predictor,
+            &return_address_stack,
+            &btb,
         );
 
         CPU {
@@ -199,7 +393,7 @@ impl CPU {
         println!("log_stats_interval: {:?}", log_stats_interval);
         let mut last_log_stats_time = Instant::now().add(log_stats_interval);
 
-        while !self.backend.exit {
+        while !self.backend.exit && self.backend.fault.is_none() {
             self.perf_counters.borrow_mut().cycle_cnt += 1;
             self.memory_subsystem.borrow_mut().do_cycle();
             self.backend.do_cycle();
@@ -220,7 +414,10 @@ impl CPU {
             self.memory_subsystem.borrow_mut().do_cycle();
         }
 
-        println!("Program complete!");
+        match &self.backend.fault {
+            None => println!("Program complete!"),
+            Some(fault) => println!("Program halted. {}", fault),
+        }
     }
 
     fn log_stats(&mut self) {
@@ -245,19 +442,32 @@ impl CPU {
         message.push_str(&format!("[Executed={}]", perf_counters.execute_cnt));
         message.push_str(&format!("[Retired={}]", perf_counters.retired_cnt));
         message.push_str(&format!("[Branch Tot={}, Pred={:.2}%]", branch_total, branch_prediction));
-        message.push_str(&format!("[Pipeline Flush={}]", perf_counters.pipeline_flushes));
+        message.push_str(&format!("[Branch Direction Miss={}]", perf_counters.branch_direction_miss_cnt));
+        message.push_str(&format!("[RAS Hit={}, Miss={}, Overflow={}]", perf_counters.ras_hit_cnt, perf_counters.ras_miss_cnt, perf_counters.ras_overflow_cnt));
+        message.push_str(&format!("[BTB Hit={}, Miss={}]", perf_counters.btb_hit_cnt, perf_counters.btb_miss_cnt));
+        message.push_str(&format!("[Unit Busy={}, Port Conflict={}]", perf_counters.unit_busy_cnt, perf_counters.port_conflict_cnt));
+        message.push_str(&format!("[Pipeline Flush={}, Branch Squash={}]", perf_counters.pipeline_flushes, perf_counters.branch_squash_cnt));
+        message.push_str(&format!("[ROB Walk={}]", perf_counters.rob_walk_cnt));
+        message.push_str(&format!("[Load Forwarded={}, Blocked={}]", perf_counters.load_forwarded_cnt, perf_counters.load_blocked_cnt));
+        message.push_str(&format!("[Memory Order Violations={}]", perf_counters.memory_order_violation_cnt));
+        message.push_str(&format!("[Acquire Blocked={}, Release Blocked={}]", perf_counters.acquire_blocked_cnt, perf_counters.release_blocked_cnt));
 
         println!("{}", message);
     }
 }
 
+// X0..X30
 pub const GENERAL_ARG_REG_CNT: u16 = 31;
-pub const SPECIAL_ARG_REG_CNT: u16 = 1;
-pub const FP: RegisterType = 11;
-pub const SP: RegisterType = 13;
-pub const LR: RegisterType = 14;
-pub const PC: RegisterType = 15;
-pub const CPSR: u16 = GENERAL_ARG_REG_CNT;
+// SP, CPSR and PC
+pub const SPECIAL_ARG_REG_CNT: u16 = 3;
+pub const FP: RegisterType = 29;
+pub const LR: RegisterType = 30;
+pub const SP: RegisterType = GENERAL_ARG_REG_CNT;
+pub const CPSR: u16 = GENERAL_ARG_REG_CNT + 1;
+// the PC can't be used as an operand; it is only used by the frontend
+pub const PC: RegisterType = GENERAL_ARG_REG_CNT + 2;
+// the zero register reads as zero and discards writes; it isn't part of the register file
+pub const XZR: RegisterType = GENERAL_ARG_REG_CNT + SPECIAL_ARG_REG_CNT;
 
 pub const ZERO_FLAG: u8 = 30;
 pub const NEGATIVE_FLAG: u8 = 31;
@@ -3,12 +3,44 @@ use crate::instructions::instructions::{Program, RegisterType};
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
-    use crate::cpu::{CPU, CPUConfig};
-    use crate::instructions::instructions::DWordType;
+    use crate::cpu::{BranchPredictorType, CPU, CPUConfig, MemoryDisambiguation, RenameRecovery, SP};
+    use crate::instructions::instructions::{DWordType, Opcode};
     use crate::loader::loader::{load_from_string, LoadError};
+    use crate::memory_subsystem::memory_subsystem::read;
 
     use super::*;
 
+    #[test]
+    fn test_cpu_config_defaults() {
+        let yaml = r#"
+phys_reg_count: 32
+memory_size: 2048
+trace:
+  decode: false
+  issue: false
+  allocate_rs: false
+  dispatch: false
+  execute: false
+  retire: false
+  pipeline_flush: false
+branch_predictor:
+  kind: gshare
+"#;
+        let cpu_config: CPUConfig = serde_yaml::from_str(yaml).unwrap();
+        let default_config = CPUConfig::default();
+        assert_eq!(cpu_config.phys_reg_count, 32);
+        assert_eq!(cpu_config.memory_size, 2048);
+        assert_eq!(cpu_config.lq_capacity, default_config.lq_capacity);
+        assert_eq!(cpu_config.memory_disambiguation, default_config.memory_disambiguation);
+        assert_eq!(cpu_config.rename_recovery, default_config.rename_recovery);
+        assert_eq!(cpu_config.ports.len(), default_config.ports.len());
+        assert_eq!(cpu_config.ras_depth, default_config.ras_depth);
+        assert_eq!(cpu_config.btb_size, default_config.btb_size);
+        assert_eq!(cpu_config.branch_predictor.kind, BranchPredictorType::Gshare);
+        assert_eq!(cpu_config.branch_predictor.table_size, default_config.branch_predictor.table_size);
+        assert!(cpu_config.latencies.is_empty());
+    }
+
     #[test]
     fn test_same_src_dst_reg() {
         let src = r#"
@@ -36,6 +68,451 @@ mod tests {
         harness.assert_reg_value(2, 110);
     }
 
+    #[test]
+    fn test_ADD_shifted_register() {
+        let src = r#"
+.text
+    MOV r0, #100;
+    MOV r1, #12;
+    ADD r2, r0, r1, LSL #2;
+    SUB r3, r0, r1, LSR #2;
+    NEG r4, r1;
+    ADD r5, r0, r4, ASR #1;
+    ADD r1, r0, r1, LSL #1;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 148);
+        harness.assert_reg_value(3, 97);
+        harness.assert_reg_value(5, 94);
+        harness.assert_reg_value(1, 124);
+    }
+
+    #[test]
+    fn test_EOR_ROR() {
+        let src = r#"
+.text
+    MOV r0, #0;
+    MOV r1, #1;
+    EOR r2, r0, r1, ROR #1;
+    ORR r3, r0, r1, LSL #4;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 0x8000000000000000);
+        harness.assert_reg_value(3, 16);
+    }
+
+    #[test]
+    fn test_ADD_extended_register() {
+        let src = r#"
+.text
+    MOV r0, #100;
+    MOV r1, #0x1FF;
+    ADD r2, r0, r1, UXTB;
+    ADD r3, r0, r1, SXTB;
+    ADD r4, r0, w1, UXTH #2;
+    MOV r5, #0xFFFFFFFF;
+    ADD r6, r0, w5, SXTW;
+    ADD r7, r0, w5, UXTW;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 355);
+        harness.assert_reg_value(3, 99);
+        harness.assert_reg_value(4, 100 + (0x1FF << 2));
+        harness.assert_reg_value(6, 99);
+        harness.assert_reg_value(7, 100 + 0xFFFFFFFF);
+    }
+
+    // Labels and variables with the name of a shift or extension are still labels and variables.
+    #[test]
+    fn test_shift_and_extend_names_as_labels() {
+        let src = r#"
+.data
+    uxth: .dword 0
+.text
+    MOV r0, #1;
+    MOV r1, #0x1FF;
+    B lsl;
+    MOV r0, #10;
+lsl:
+    ADD r2, r0, r1, LSL #1;
+    ADD r3, r0, r1, uxtb;
+    ADD r4, r0, w1, UXTH #2;
+    LSL r5, r0, #3;
+    ADD r7, r0, w1, uxtw;
+    CBZ r5, uxtw;
+    ADR r6, uxth;
+    B ror;
+uxtw:
+    MOV r0, #10;
+ror:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(0, 1);
+        harness.assert_reg_value(2, 1 + (0x1FF << 1));
+        harness.assert_reg_value(3, 1 + 0xFF);
+        harness.assert_reg_value(4, 1 + (0x1FF << 2));
+        harness.assert_reg_value(5, 8);
+        harness.assert_reg_value(6, 0);
+        harness.assert_reg_value(7, 1 + 0x1FF);
+    }
+
+    #[test]
+    fn test_CMP_shifted_register() {
+        let src = r#"
+.text
+    MOV r0, #40;
+    MOV r1, #5;
+    CMP r0, r1, LSL #3;
+    BNE fail;
+    B end;
+fail:
+    MOV r2, #1;
+end:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 0);
+    }
+
+    #[test]
+    fn test_LSL_LSR_ASR_ROR() {
+        let src = r#"
+.text
+    MOV r0, #0x81;
+    LSL r1, r0, #4;
+    LSR r2, r0, #4;
+    NEG r3, r0;
+    ASR r4, r3, #4;
+    LSR r5, r3, #60;
+    ROR r6, r0, #4;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 0x810);
+        harness.assert_reg_value(2, 0x8);
+        harness.assert_reg_value(4, -9i64 as DWordType);
+        harness.assert_reg_value(5, 0xF);
+        harness.assert_reg_value(6, 0x1000000000000008);
+    }
+
+    // The register amount is taken modulo 64.
+    #[test]
+    fn test_LSL_register_amount() {
+        let src = r#"
+.text
+    MOV r0, #3;
+    MOV r1, #2;
+    LSL r2, r0, r1;
+    MOV r3, #65;
+    LSL r4, r0, r3;
+    MOV r5, #64;
+    ROR r6, r0, r5;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 12);
+        harness.assert_reg_value(4, 6);
+        harness.assert_reg_value(6, 3);
+    }
+
+    #[test]
+    fn test_UBFX_SBFX_BFI_BFXIL() {
+        let src = r#"
+.text
+    LDR r0, =0x12345678ABCD;
+    UBFX r1, r0, #8, #16;
+    SBFX r2, r0, #8, #16;
+    SBFX r3, r0, #0, #12;
+    MOV r4, #-1;
+    BFI r4, r0, #16, #8;
+    MOV r5, #0;
+    BFXIL r5, r0, #32, #16;
+    UBFX r6, r0, #0, #64;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 0x78AB);
+        harness.assert_reg_value(2, 0x78AB);
+        harness.assert_reg_value(3, 0xFFFFFFFFFFFFFBCD);
+        harness.assert_reg_value(4, 0xFFFFFFFFFFCDFFFF);
+        harness.assert_reg_value(5, 0x1234);
+        harness.assert_reg_value(6, 0x12345678ABCD);
+    }
+
+    #[test]
+    fn test_CLZ_CLS_RBIT_REV() {
+        let src = r#"
+.text
+    MOV r0, #0xFF;
+    CLZ r1, r0;
+    CLS r2, r0;
+    MOV r3, #-2;
+    CLS r4, r3;
+    CLZ r5, r3;
+    RBIT r6, r0;
+    LDR r7, =0x0102030405060708;
+    REV r8, r7;
+    REV16 r9, r7;
+    REV32 r10, r7;
+    MOV r11, #0;
+    CLZ r12, r11;
+    CLS r13, r11;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 56);
+        harness.assert_reg_value(2, 55);
+        harness.assert_reg_value(4, 62);
+        harness.assert_reg_value(5, 0);
+        harness.assert_reg_value(6, 0xFF00000000000000);
+        harness.assert_reg_value(8, 0x0807060504030201);
+        harness.assert_reg_value(9, 0x0201040306050807);
+        harness.assert_reg_value(10, 0x0403020108070605);
+        harness.assert_reg_value(12, 64);
+        harness.assert_reg_value(13, 63);
+    }
+
+    #[test]
+    fn test_CSEL_CSINC_CSINV_CSNEG() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    MOV r1, #20;
+    CMP r0, r1;
+    CSEL r2, r0, r1, LT;
+    CSEL r3, r0, r1, GE;
+    CSINC r4, r0, r1, EQ;
+    CSINV r5, r0, r1, EQ;
+    CSNEG r6, r0, r1, EQ;
+    CSNEG r7, r0, r1, NE;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 10);
+        harness.assert_reg_value(3, 20);
+        harness.assert_reg_value(4, 21);
+        harness.assert_reg_value(5, !20);
+        harness.assert_reg_value(6, -20i64 as DWordType);
+        harness.assert_reg_value(7, 10);
+    }
+
+    #[test]
+    fn test_CSET_CSETM_CINC() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    CMP r0, #10;
+    CSET r1, EQ;
+    CSET r2, NE;
+    CSETM r3, eq;
+    CSETM r4, ne;
+    CINC r5, r0, LE;
+    CINC r6, r0, GT;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 1);
+        harness.assert_reg_value(2, 0);
+        harness.assert_reg_value(3, DWordType::MAX);
+        harness.assert_reg_value(4, 0);
+        harness.assert_reg_value(5, 11);
+        harness.assert_reg_value(6, 10);
+    }
+
+    // A label with the name of a condition code is still a label; in the position of a condition it is the condition.
+    #[test]
+    fn test_condition_code_as_label() {
+        let src = r#"
+.data
+    eq: .dword 0
+.text
+    MOV r0, #1;
+    B lt;
+    MOV r0, #10;
+lt:
+    ADD r0, r0, #1;
+    CMP r0, #2;
+    CSET r1, eq;
+    CSEL r2, r0, r1, lt;
+    ADR r3, eq;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(0, 2);
+        harness.assert_reg_value(1, 1);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(3, 0);
+    }
+
+    // The branchless version of a loop with a hard to predict branch.
+    #[test]
+    fn test_CINC_loop() {
+        let src = r#"
+.text
+    MOV r0, #100;
+    MOV r2, #0;
+    MOV r3, #0;
+loop:
+    SUB r0, r0, #1;
+    TST r0, #1;
+    CINC r2, r2, NE;
+    CINC r3, r3, EQ;
+    CBNZ r0, loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 50);
+        harness.assert_reg_value(3, 50);
+    }
+
+    #[test]
+    fn test_ADDS_SUBS_flags() {
+        let src = r#"
+.text
+    MOV r0, #0x7FFFFFFFFFFFFFFF;
+    ADDS r1, r0, #1;
+    CSET r2, VS;
+    CSET r3, MI;
+    CSET r4, CS;
+    SUBS r5, r0, r0;
+    CSET r6, EQ;
+    CSET r7, HS;
+    MOV r8, #0xFFFFFFFFFFFFFFFF;
+    ADDS r9, r8, #1;
+    CSET r10, CS;
+    CSET r11, EQ;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 0x8000000000000000);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(3, 1);
+        harness.assert_reg_value(4, 0);
+        harness.assert_reg_value(5, 0);
+        harness.assert_reg_value(6, 1);
+        harness.assert_reg_value(7, 1);
+        harness.assert_reg_value(9, 0);
+        harness.assert_reg_value(10, 1);
+        harness.assert_reg_value(11, 1);
+    }
+
+    #[test]
+    fn test_CMP_carry() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    CMP r0, #10;
+    CSET r1, HS;
+    CMP r0, #11;
+    CSET r2, LO;
+    CSET r3, LT;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 1);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(3, 1);
+    }
+
+    // a == 1 && b > 2 without branches.
+    #[test]
+    fn test_CCMP() {
+        let src = r#"
+.text
+    MOV r0, #1;
+    MOV r1, #3;
+    CMP r0, #1;
+    CCMP r1, #2, #0, EQ;
+    CSET r2, GT;
+    MOV r3, #2;
+    CMP r0, #1;
+    CCMP r3, #2, #0, EQ;
+    CSET r4, GT;
+    CMP r0, #5;
+    CCMP r1, #2, #4, EQ;
+    CSET r5, EQ;
+    CSET r6, GT;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(4, 0);
+        harness.assert_reg_value(5, 1);
+        harness.assert_reg_value(6, 0);
+    }
+
+    #[test]
+    fn test_CCMN() {
+        let src = r#"
+.text
+    MOV r0, #-3;
+    MOV r1, #3;
+    CMP r1, #3;
+    CCMN r0, r1, #0, EQ;
+    CSET r2, EQ;
+    CMP r1, #4;
+    CCMN r0, #3, #2, EQ;
+    CSET r3, CS;
+    CSET r4, EQ;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(3, 1);
+        harness.assert_reg_value(4, 0);
+    }
+
+    #[test]
+    fn test_ANDS() {
+        let src = r#"
+.text
+    MOV r0, #0xF0;
+    CMP r0, #0;
+    ANDS r1, r0, #0x0F;
+    CSET r2, EQ;
+    CSET r3, CS;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 0);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(3, 0);
+    }
+
+    // 128 bits addition and subtraction using carry chaining.
+    #[test]
+    fn test_ADCS_SBCS_carry_chain() {
+        let src = r#"
+.text
+    MOV r0, #0xFFFFFFFFFFFFFFFF;
+    MOV r1, #1;
+    MOV r2, #1;
+    MOV r3, #2;
+    ADDS r4, r0, r2;
+    ADC r5, r1, r3;
+    MOV r6, #0;
+    MOV r7, #5;
+    SUBS r8, r6, r2;
+    SBCS r9, r7, r3;
+    CSET r10, HS;
+    ADDS r11, r0, r0;
+    ADCS r12, r6, r6;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(4, 0);
+        harness.assert_reg_value(5, 4);
+        harness.assert_reg_value(8, 0xFFFFFFFFFFFFFFFF);
+        harness.assert_reg_value(9, 2);
+        harness.assert_reg_value(10, 1);
+        harness.assert_reg_value(11, 0xFFFFFFFFFFFFFFFE);
+        harness.assert_reg_value(12, 1);
+    }
+
     #[test]
     fn test_NEG() {
         let src = r#"
@@ -173,64 +650,293 @@ mod tests {
     }
 
     #[test]
-    fn test_MUL() {
+    fn test_MOVZ_MOVN_MOVK() {
         let src = r#"
 .text
-    MOV r0, #100;
-    MOV r1, #10;
-    MUL r2, r0, r1;
+    MOVZ r0, #0x1234;
+    MOVZ r1, #0x1234, LSL #32;
+    MOVN r2, #0x1234, LSL #16;
+    MOVZ r3, #0xBEEF;
+    MOVK r3, #0xDEAD, LSL #16;
+    MOVK r3, #0xCAFE, LSL #48;
+    MOV r4, #-1;
+    MOVK r4, #0, LSL #48;
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
-        harness.assert_reg_value(0, 100);
-        harness.assert_reg_value(1, 10);
-        harness.assert_reg_value(2, 1000);
+        harness.assert_reg_value(0, 0x1234);
+        harness.assert_reg_value(1, 0x1234 << 32);
+        harness.assert_reg_value(2, !(0x1234 << 16));
+        harness.assert_reg_value(3, 0xCAFE0000DEADBEEF);
+        harness.assert_reg_value(4, 0x0000FFFFFFFFFFFF);
     }
 
+    // The constants that can't be encoded by a MOV are expanded into a MOVZ or MOVN followed by MOVKs.
     #[test]
-    fn test_loop_CMP_BNE() {
+    fn test_LDR_constant() {
         let src = r#"
 .text
-    MOV r0, #10;
-    MOV r1, #0;
-loop:
-    SUB r0, r0, #1;
-    PRINTR r0;
-    ADD r1, r1, #1;
-    CMP r0, #0;
-    BNE loop;
+    LDR r0, =0x12345678;
+    LDR r1, =0xFFFFFFFF12345678;
+    LDR r2, =-1;
+    LDR r3, =0x123456789ABCDEF0;
+    B end;
+    MOV r4, #1;
+end:
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
-
-        harness.assert_reg_value(0, 0);
-        harness.assert_reg_value(1, 10);
+        harness.assert_reg_value(0, 0x12345678);
+        harness.assert_reg_value(1, 0xFFFFFFFF12345678);
+        harness.assert_reg_value(2, DWordType::MAX);
+        harness.assert_reg_value(3, 0x123456789ABCDEF0);
+        harness.assert_reg_value(4, 0);
+        // 2 + 2 + 1 + 4 instructions for the constants, followed by the B and the MOV.
+        assert_eq!(harness.program.as_ref().unwrap().code.len(), 11);
     }
 
     #[test]
-    fn test_loop_CMP_BGT() {
+    fn test_LDR_constant_W() {
         let src = r#"
 .text
-    MOV r0, #10;
-    MOV r1, #0;
-loop:
-    SUB r0, r0, #1;
-    ADD r1, r1, #1;
-    CMP r0, #1;
-    BGT loop;
+    MOV r0, #-1;
+    LDR w0, =0x12345678;
+    LDR w1, =0xFFFF1234;
+    LDR w2, =-2;
+    LDR w3, =0xFF00FF;
+    B end;
+    MOV r4, #1;
+end:
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
+        harness.assert_reg_value(0, 0x12345678);
+        harness.assert_reg_value(1, 0xFFFF1234);
+        harness.assert_reg_value(2, 0xFFFFFFFE);
+        harness.assert_reg_value(3, 0xFF00FF);
+        harness.assert_reg_value(4, 0);
+        // the MOV, 2 + 1 + 1 + 1 instructions for the constants, followed by the B and the MOV.
+        assert_eq!(harness.program.as_ref().unwrap().code.len(), 8);
+    }
 
-        harness.assert_reg_value(0, 1);
-        harness.assert_reg_value(1, 9);
+    #[test]
+    fn test_LDR_constant_W_out_of_range() {
+        let src = ".text\n    LDR w0, =0x123456789;\n".to_string();
+        match load_from_string(CPUConfig::default(), src) {
+            Err(LoadError::AnalysisError(msg_vec)) => assert!(msg_vec[0].contains("doesn't fit")),
+            _ => panic!("LDR w0, =0x123456789 should be rejected"),
+        }
     }
 
     #[test]
-    fn test_loop_CMP_BGE() {
+    fn test_MOV_immediate_encoding() {
+        let encodable = ["#0xFFFF0000", "#-2", "#0x5555555555555555", "#0x00FF00FF00FF00FF", "#0x7FFFFFFFFFFFFFFF"];
+        for immediate in encodable {
+            let src = format!(".text\n    MOV r0, {};\n", immediate);
+            assert!(load_from_string(CPUConfig::default(), src).is_ok(), "MOV {} should be encodable", immediate);
+        }
+
+        let src = ".text\n    MOV r0, #0x12345678;\n".to_string();
+        match load_from_string(CPUConfig::default(), src) {
+            Err(LoadError::AnalysisError(msg_vec)) => assert!(msg_vec[0].contains("can't be encoded")),
+            _ => panic!("MOV #0x12345678 should not be encodable"),
+        }
+    }
+
+    #[test]
+    fn test_MUL() {
         let src = r#"
 .text
-    MOV r0, #10;
+    MOV r0, #100;
+    MOV r1, #10;
+    MUL r2, r0, r1;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(0, 100);
+        harness.assert_reg_value(1, 10);
+        harness.assert_reg_value(2, 1000);
+    }
+
+    #[test]
+    fn test_MADD_MSUB_MNEG() {
+        let src = r#"
+.text
+    MOV r0, #100;
+    MOV r1, #10;
+    MOV r2, #5;
+    MADD r3, r0, r1, r2;
+    MSUB r4, r0, r1, r2;
+    MNEG r5, r0, r1;
+    MADD r2, r2, r2, r2;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(3, 1005);
+        harness.assert_reg_value(4, -995i64 as DWordType);
+        harness.assert_reg_value(5, -1000i64 as DWordType);
+        harness.assert_reg_value(2, 30);
+    }
+
+    #[test]
+    fn test_SMULL_UMULL_SMULH_UMULH() {
+        let src = r#"
+.text
+    MOV r0, #0xFFFFFFFF;
+    MOV r1, #2;
+    SMULL r2, w0, w1;
+    UMULL r3, w0, w1;
+    MOV r4, #-1;
+    SMULH r5, r4, r1;
+    UMULH r6, r4, r1;
+    UMULH r7, r4, r4;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, -2i64 as DWordType);
+        harness.assert_reg_value(3, 0x1FFFFFFFE);
+        harness.assert_reg_value(5, DWordType::MAX);
+        harness.assert_reg_value(6, 1);
+        harness.assert_reg_value(7, 0xFFFFFFFFFFFFFFFE);
+    }
+
+    // A division by zero gives 0 instead of a fault.
+    #[test]
+    fn test_SDIV_UDIV() {
+        let src = r#"
+.text
+    MOV r0, #-100;
+    MOV r1, #10;
+    MOV r2, #0;
+    SDIV r3, r0, r1;
+    UDIV r4, r0, r1;
+    SDIV r5, r0, r2;
+    UDIV r6, r1, r2;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(3, -10i64 as DWordType);
+        harness.assert_reg_value(4, (-100i64 as DWordType) / 10);
+        harness.assert_reg_value(5, 0);
+        harness.assert_reg_value(6, 0);
+    }
+
+    // The divider isn't pipelined, so the second SDIV needs to wait for the first one.
+    #[test]
+    fn test_SDIV_not_pipelined() {
+        let src = r#"
+.text
+    MOV r0, #100;
+    MOV r1, #5;
+    SDIV r2, r0, r1;
+    SDIV r3, r0, r1;
+    ADD r4, r2, r3;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(4, 40);
+        assert!(harness.cpu.as_ref().unwrap().perf_counters.borrow().unit_busy_cnt > 0);
+    }
+
+    #[test]
+    fn test_latency_override() {
+        let src = r#"
+.text
+    MOV r0, #100;
+    MOV r1, #10;
+    MUL r2, r0, r1;
+    MUL r3, r2, r1;
+"#;
+        let mut cycles = Vec::new();
+        for latency in [1, 20] {
+            let mut cpu_config = TestHarness::new_test_cpu_config();
+            cpu_config.latencies.insert(Opcode::MUL, latency);
+            let mut harness = TestHarness::new(cpu_config);
+            harness.run(src);
+            harness.assert_reg_value(3, 10000);
+            cycles.push(harness.cpu.as_ref().unwrap().perf_counters.borrow().cycle_cnt);
+        }
+
+        // the MULs are dependent, so the latency can't be hidden
+        assert!(cycles[1] >= cycles[0] + 2 * 19);
+    }
+
+    // With a single ALU port, only one of the independent ADDs can be dispatched per cycle.
+    #[test]
+    fn test_port_conflict() {
+        let src = r#"
+.text
+    MOV r0, #1;
+    MOV r1, #2;
+    MOV r2, #3;
+    MOV r3, #4;
+    ADD r4, r0, r1;
+    ADD r5, r2, r3;
+"#;
+        let mut cpu_config = TestHarness::new_test_cpu_config();
+        cpu_config.ports.retain(|port| port.name != "alu1");
+        let mut harness = TestHarness::new(cpu_config);
+        harness.run(src);
+        harness.assert_reg_value(4, 3);
+        harness.assert_reg_value(5, 7);
+
+        let perf_counters = harness.cpu.as_ref().unwrap().perf_counters.borrow();
+        assert!(perf_counters.port_conflict_cnt > 0);
+        assert_eq!(perf_counters.port_dispatch_cnt.iter().sum::<u64>(), perf_counters.dispatch_cnt);
+    }
+
+    #[test]
+    #[should_panic(expected = "there is no port for unit type Branch")]
+    fn test_no_port_for_unit_type() {
+        let mut cpu_config = TestHarness::new_test_cpu_config();
+        cpu_config.ports.retain(|port| port.name != "branch");
+        TestHarness::new(cpu_config);
+    }
+
+    #[test]
+    fn test_loop_CMP_BNE() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    MOV r1, #0;
+loop:
+    SUB r0, r0, #1;
+    PRINTR r0;
+    ADD r1, r1, #1;
+    CMP r0, #0;
+    BNE loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 0);
+        harness.assert_reg_value(1, 10);
+    }
+
+    #[test]
+    fn test_loop_CMP_BGT() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    MOV r1, #0;
+loop:
+    SUB r0, r0, #1;
+    ADD r1, r1, #1;
+    CMP r0, #1;
+    BGT loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 1);
+        harness.assert_reg_value(1, 9);
+    }
+
+    #[test]
+    fn test_loop_CMP_BGE() {
+        let src = r#"
+.text
+    MOV r0, #10;
     MOV r1, #0;
 loop:
     SUB r0, r0, #1;
@@ -296,6 +1002,122 @@ end:
         harness.assert_reg_value(1, 10);
     }
 
+    #[test]
+    fn test_loop_CMP_B_HI() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    MOV r1, #0;
+loop:
+    SUB r0, r0, #1;
+    ADD r1, r1, #1;
+    CMP r0, #1;
+    B.HI loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 1);
+        harness.assert_reg_value(1, 9);
+    }
+
+    #[test]
+    fn test_B_cond_unsigned() {
+        let src = r#"
+.text
+    MOV r0, #-1;
+    CMP r0, #1;
+    B.HI hi_taken;
+    B end;
+hi_taken:
+    MOV r1, #1;
+    B.LS end;
+    B.GT end;
+    MOV r2, #1;
+    B.HS hs_taken;
+    B end;
+hs_taken:
+    MOV r3, #1;
+    b.lo end;
+    MOV r4, #1;
+    MOV r5, #1;
+    CMP r5, r0;
+    B.LO lo_taken;
+    B end;
+lo_taken:
+    MOV r6, #1;
+end:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(1, 1);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(3, 1);
+        harness.assert_reg_value(4, 1);
+        harness.assert_reg_value(6, 1);
+    }
+
+    #[test]
+    fn test_B_MI_PL_VS_VC() {
+        let src = r#"
+.text
+    MOV r0, #0;
+    SUBS r1, r0, #1;
+    B.MI mi_taken;
+    B end;
+mi_taken:
+    MOV r2, #1;
+    B.PL end;
+    MOV r3, #1;
+    MOV r4, #1;
+    LSL r4, r4, #63;
+    SUBS r5, r4, #1;
+    B.VS vs_taken;
+    B end;
+vs_taken:
+    MOV r6, #1;
+    B.VC end;
+    MOV r7, #1;
+end:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(3, 1);
+        harness.assert_reg_value(6, 1);
+        harness.assert_reg_value(7, 1);
+    }
+
+    // The direction of the CBZ alternates; every predictor should produce the same result.
+    #[test]
+    fn test_branch_predictors() {
+        let src = r#"
+.text
+    MOV r0, #20;
+loop:
+    SUB r0, r0, #1;
+    AND r1, r0, #1;
+    CBZ r1, even;
+    ADD r2, r2, #1;
+    B next;
+even:
+    ADD r3, r3, #1;
+next:
+    CBNZ r0, loop;
+"#;
+        for kind in [BranchPredictorType::Static, BranchPredictorType::Bimodal, BranchPredictorType::Gshare, BranchPredictorType::Tage] {
+            let mut cpu_config = TestHarness::new_test_cpu_config();
+            cpu_config.branch_predictor.kind = kind;
+            let mut harness = TestHarness::new(cpu_config);
+            harness.run(src);
+
+            harness.assert_reg_value(2, 10);
+            harness.assert_reg_value(3, 10);
+        }
+    }
+
     #[test]
     fn test_TEQ() {
         let src = r#"
@@ -313,131 +1135,794 @@ end:
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
-        harness.assert_reg_value(2, 0); // Ensure fail indicator is not set
+        harness.assert_reg_value(2, 0); // Ensure fail indicator is not set
+    }
+
+    #[test]
+    fn test_TST() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    MOV r1, #12;
+    TST r0, r1;
+    BEQ fail;
+    TST r0, #5;
+    BNE fail;
+    B end;
+fail:
+    MOV r2, #1;
+end:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(2, 0); // Ensure fail indicator is not set
+    }
+
+    #[test]
+    fn test_LDR_STR() {
+        let src = r#"
+.data
+    var_a: .dword 5
+    var_b: .dword 10
+    var_c: .dword 0
+.text
+    MOV r0, =var_a;
+    LDR r0, [r0];
+    MOV r1, =var_b;
+    LDR r1, [r1];
+    ADD r2, r0, r1;
+    MOV r0, =var_c;
+    STR r2, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_variable_value("var_c", 15);
+    }
+
+    #[test]
+    fn test_LDR() {
+        let src = r#"
+.data
+    var_a: .dword 5
+.text
+    MOV r0, =var_a;
+    LDR r0, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 5);
+    }
+
+    #[test]
+    fn test_STR() {
+        let src = r#"
+.data
+    var_a: .dword 0
+.text
+    MOV r0, =var_a;
+    MOV r1, #10;
+    STR r1, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_variable_value("var_a", 10);
+    }
+
+    // Ensures that stores update to memory out of order even they can be performed in order.
+    #[test]
+    fn test_STR_WAW() {
+        let src = r#"
+.data
+    var_a: .dword 0
+.text
+    mov r0, =var_a;
+    mov r1, #1;
+    str r1, [r0];
+    mov r2, #2;
+    str r2, [r0];
+    mov r3, #3;
+    str r3, [r0];
+    mov r4, #4;
+    str r4, [r0];
+    mov r5, #5;
+    str r5, [r0];
+    mov r6, #6;
+    str r6, [r0];
+    mov r7, #7;
+    str r7, [r0];
+    mov r8, #8;
+    str r8, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_variable_value("var_a", 8);
+    }
+
+    #[test]
+    fn test_STR_loop() {
+        let src = r#"
+.data
+    var_a: .dword 0
+.text
+    MOV r0, #100;
+    MOV r1, =var_a;
+    MOV r2, #0;
+loop:
+    ADD r2, r2, #1;
+    SUB r0, r0, #1;
+    STR r2, [r1];
+    CBNZ r0, loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_variable_value("var_a", 100);
+    }
+
+    #[test]
+    fn test_STR_LDR_forwarding() {
+        let src = r#"
+.data
+    var_a: .dword 0
+.text
+    MOV r0, =var_a;
+    MOV r1, #10;
+    STR r1, [r0];
+    LDR r2, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 10);
+        harness.assert_variable_value("var_a", 10);
+    }
+
+    // The address of the store is only known after the first load completes; the second load
+    // is ready earlier and should wait for the store instead of reading stale memory.
+    #[test]
+    fn test_LDR_blocked_on_unknown_store_address() {
+        let src = r#"
+.data
+    var_a: .dword 0
+    var_b: .dword 0
+.text
+    MOV r0, =var_b;
+    LDR r1, [r0];
+    MOV r2, #20;
+    STR r2, [r1];
+    MOV r3, =var_a;
+    LDR r4, [r3];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(4, 20);
+        harness.assert_variable_value("var_a", 20);
+    }
+
+    // Same as the previous test, but now the load speculates that the store doesn't alias. This
+    // speculation is wrong and the load should be replayed.
+    #[test]
+    fn test_LDR_memory_order_violation() {
+        let src = r#"
+.data
+    var_a: .dword 0
+    var_b: .dword 0
+.text
+    MOV r0, =var_b;
+    LDR r1, [r0];
+    MOV r2, #20;
+    STR r2, [r1];
+    MOV r3, =var_a;
+    LDR r4, [r3];
+"#;
+        let mut cpu_config = TestHarness::new_test_cpu_config();
+        cpu_config.memory_disambiguation = MemoryDisambiguation::Aggressive;
+        let mut harness = TestHarness::new(cpu_config);
+        harness.run(src);
+
+        harness.assert_reg_value(4, 20);
+        harness.assert_variable_value("var_a", 20);
+        assert!(harness.cpu.as_ref().unwrap().perf_counters.borrow().memory_order_violation_cnt > 0);
+    }
+
+    #[test]
+    fn test_LDRB_LDRH_LDR_W() {
+        let src = r#"
+.data
+    var_a: .byte 0xF1
+    var_b: .hword 0xF2F3
+    var_c: .word 0xF4F5F6F7
+    var_d: .dword 0xFFFFFFFFFFFFFFFF
+.text
+    MOV r0, =var_a;
+    LDRB w1, [r0];
+    MOV r0, =var_b;
+    LDRH w2, [r0];
+    MOV r0, =var_c;
+    LDR w3, [r0];
+    MOV r0, =var_d;
+    LDRB w4, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(1, 0xF1);
+        harness.assert_reg_value(2, 0xF2F3);
+        harness.assert_reg_value(3, 0xF4F5F6F7);
+        harness.assert_reg_value(4, 0xFF);
+    }
+
+    #[test]
+    fn test_LDRSB_LDRSH_LDRSW() {
+        let src = r#"
+.data
+    var_a: .byte 0xFE
+    var_b: .hword 0x8000
+    var_c: .word 0xFFFFFFFF
+    var_d: .word 0x7FFFFFFF
+.text
+    MOV r0, =var_a;
+    LDRSB r1, [r0];
+    MOV r0, =var_b;
+    LDRSH r2, [r0];
+    MOV r0, =var_c;
+    LDRSW r3, [r0];
+    MOV r0, =var_d;
+    LDRSW r4, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(1, -2i64 as DWordType);
+        harness.assert_reg_value(2, -32768i64 as DWordType);
+        harness.assert_reg_value(3, -1i64 as DWordType);
+        harness.assert_reg_value(4, 0x7FFFFFFF);
+    }
+
+    // A signed byte or halfword load into a W register sign extends to 32 bits and zero extends the rest.
+    #[test]
+    fn test_LDRSB_LDRSH_W() {
+        let src = r#"
+.data
+    var_a: .byte 0xFE
+    var_b: .hword 0x8000
+.text
+    MOV r1, #-1;
+    MOV r0, =var_a;
+    LDRSB w1, [r0];
+    MOV r0, =var_b;
+    LDRSH w2, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(1, 0xFFFFFFFE);
+        harness.assert_reg_value(2, 0xFFFF8000);
+    }
+
+    #[test]
+    fn test_load_store_register_view_invalid() {
+        let invalid = ["LDRB x0, [x1]", "LDRH x0, [x1]", "STRB x0, [x1]", "STRH x0, [x1]", "LDRSW w0, [x1]"];
+        for instr in invalid {
+            let src = format!(".text\n    {};\n", instr);
+            assert!(matches!(load_from_string(CPUConfig::default(), src), Err(LoadError::AnalysisError(_))),
+                    "{} should be rejected", instr);
+        }
+    }
+
+    #[test]
+    fn test_STRB_STRH_STR_W() {
+        let src = r#"
+.data
+    var_a: .byte 0
+    var_b: .hword 0
+    var_c: .word 0
+    var_d: .dword 0
+.text
+    MOV r1, #0x1FF;
+    MOV r0, =var_a;
+    STRB w1, [r0];
+    MOV r0, =var_b;
+    STRH w1, [r0];
+    MOV r0, =var_c;
+    STR w1, [r0];
+    MOV r0, =var_d;
+    STR r1, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_variable_value("var_a", 0xFF);
+        harness.assert_variable_value("var_b", 0x1FF);
+        harness.assert_variable_value("var_c", 0x1FF);
+        harness.assert_variable_value("var_d", 0x1FF);
+    }
+
+    // A narrow store is forwarded to a narrow load of the same bytes; a wide load that only
+    // partially overlaps with the store needs to wait till the store is written to memory.
+    #[test]
+    fn test_STRB_LDR_partial_overlap() {
+        let src = r#"
+.data
+    var_a: .dword 0x1111111111111111
+.text
+    MOV r0, =var_a;
+    MOV r1, #0x22;
+    STRB w1, [r0];
+    LDRB w2, [r0];
+    LDR r3, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 0x22);
+        harness.assert_reg_value(3, 0x1111111111111122);
+        harness.assert_variable_value("var_a", 0x1111111111111122);
+    }
+
+    #[test]
+    fn test_LDR_STR_offset() {
+        let src = r#"
+.data
+    var_a: .dword 10
+    var_b: .dword 20
+    var_c: .dword 0
+.text
+    MOV r0, =var_a;
+    LDR r1, [r0];
+    LDR r2, [r0, #8];
+    ADD r3, r1, r2;
+    STR r3, [r0, #16];
+    MOV r4, =var_c;
+    LDR r5, [r4, #-16];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 20);
+        harness.assert_reg_value(5, 10);
+        harness.assert_variable_value("var_c", 30);
+    }
+
+    #[test]
+    fn test_ADR_variable() {
+        let src = r#"
+.data
+    var_a: .dword 10
+    var_b: .dword 20
+.text
+    NOP;
+    ADR r0, var_b;
+    LDR r1, [r0];
+    ADR r2, var_a;
+    STR r1, [r2];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 8);
+        harness.assert_reg_value(2, 0);
+        harness.assert_variable_value("var_a", 20);
+    }
+
+    // The address of a label is the index of the instruction.
+    #[test]
+    fn test_ADR_label() {
+        let src = r#"
+.text
+    ADR r0, target;
+    BX r0;
+    MOV r1, #1;
+target:
+    MOV r2, #1;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 3);
+        harness.assert_reg_value(1, 0);
+        harness.assert_reg_value(2, 1);
+    }
+
+    #[test]
+    fn test_ADRP_lo12() {
+        let src = r#"
+.data
+    var_a: .dword 10
+    var_b: .dword 20
+.text
+    ADRP r0, var_b;
+    ADD r0, r0, :lo12:var_b;
+    LDR r1, [r0];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 8);
+        harness.assert_reg_value(1, 20);
+    }
+
+    #[test]
+    fn test_LDR_STR_pre_and_post_index() {
+        let src = r#"
+.text
+    MOV r1, #10;
+    MOV r2, #20;
+    STR r1, [sp, #-16]!;
+    STR r2, [sp, #8];
+    LDR r3, [sp], #16;
+    LDR r4, [sp, #-8];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(3, 10);
+        harness.assert_reg_value(4, 20);
+        harness.assert_reg_value(SP, harness.cpu_config.memory_size as DWordType);
+    }
+
+    #[test]
+    fn test_LDR_STR_register_offset() {
+        let src = r#"
+.data
+    var_a: .dword 10
+    var_b: .dword 20
+    var_c: .dword 30
+    var_d: .hword 0
+    var_e: .hword 0
+.text
+    MOV r0, =var_a;
+    MOV r1, #2;
+    LDR r2, [r0, r1, LSL #3];
+    MOV r3, #8;
+    LDR r4, [r0, r3];
+    MOV r5, =var_d;
+    MOV r6, #1;
+    STRH w2, [r5, r6, LSL #1];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 30);
+        harness.assert_reg_value(4, 20);
+        harness.assert_variable_value("var_e", 30);
+    }
+
+    // The base register is incremented by the post-index addressing mode on every iteration.
+    #[test]
+    fn test_STR_post_index_loop() {
+        let src = r#"
+.data
+    var_a: .dword 0
+    var_b: .dword 0
+    var_c: .dword 0
+    var_d: .dword 0
+.text
+    MOV r0, =var_a;
+    MOV r1, #4;
+loop:
+    STR r1, [r0], #8;
+    SUB r1, r1, #1;
+    CBNZ r1, loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_variable_value("var_a", 4);
+        harness.assert_variable_value("var_b", 3);
+        harness.assert_variable_value("var_c", 2);
+        harness.assert_variable_value("var_d", 1);
+    }
+
+    // The prologue pushes and the epilogue pops the frame and the callee saved registers.
+    #[test]
+    fn test_STP_LDP_stack_push_pop() {
+        let src = r#"
+.global _start
+.text
+_callee:
+    STP fp, lr, [sp, #-16]!;
+    STP r19, r20, [sp, #-16]!;
+    MOV r19, #100;
+    MOV r20, #200;
+    ADD r0, r19, r20;
+    LDP r19, r20, [sp], #16;
+    LDP fp, lr, [sp], #16;
+    RET;
+_start:
+    MOV r19, #1;
+    MOV r20, #2;
+    BL _callee;
+    ADD r0, r0, r19;
+    ADD r0, r0, r20;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(0, 303);
+        harness.assert_reg_value(19, 1);
+        harness.assert_reg_value(20, 2);
+        harness.assert_reg_value(SP, harness.cpu_config.memory_size as DWordType);
+    }
+
+    #[test]
+    fn test_STP_LDP_offset() {
+        let src = r#"
+.data
+    var_a: .dword 0
+    var_b: .word 0
+    var_c: .word 0
+    var_d: .dword 0
+.text
+    ADR r0, var_a;
+    MOV r1, #10;
+    MOV r2, #20;
+    STP w1, w2, [r0, #8];
+    LDP r3, r4, [r0];
+    LDP w5, w6, [r0, #8];
+    STP r5, r6, [r0, #8];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(3, 0);
+        harness.assert_reg_value(4, (20 << 32) | 10);
+        harness.assert_reg_value(5, 10);
+        harness.assert_reg_value(6, 20);
+        harness.assert_variable_value("var_b", 10);
+        harness.assert_variable_value("var_c", 0);
+        harness.assert_variable_value("var_d", 20);
+    }
+
+    #[test]
+    fn test_STP_LDP_invalid() {
+        let invalid = ["LDP r1, r1, [sp]", "STP r1, w2, [sp]", "STP r1, r2, [sp, #12]", "STP r1, r2, [sp, #512]",
+            "LDP r1, r2, [sp, r3]", "LDP r1, sp, [sp], #16"];
+        for instr in invalid {
+            let src = format!(".text\n    {};\n", instr);
+            assert!(matches!(load_from_string(CPUConfig::default(), src), Err(LoadError::AnalysisError(_))),
+                    "{} should be rejected", instr);
+        }
+    }
+
+    #[test]
+    fn test_XZR() {
+        let src = r#"
+.text
+    MOV x0, #5;
+    MOV xzr, #7;
+    ADD x1, x0, xzr;
+    ORR x2, xzr, #12;
+    SUBS xzr, x0, #5;
+    CSET x3, EQ;
+    CSINC w4, wzr, wzr, EQ;
+    MOV x5, #1;
+    STR x5, [sp, #-8]!;
+    LDR xzr, [sp], #8;
+    MOV x6, xzr;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_reg_value(1, 5);
+        harness.assert_reg_value(2, 12);
+        harness.assert_reg_value(3, 1);
+        harness.assert_reg_value(4, 0);
+        harness.assert_reg_value(6, 0);
+    }
+
+    #[test]
+    fn test_X_registers_and_SP() {
+        let src = r#"
+.text
+    MOV x29, #3;
+    ADD x30, x29, #1;
+    MOV r13, #9;
+    SUB sp, sp, #16;
+    MOV x0, sp;
+    ADD x1, sp, #8;
+    CMP sp, x0;
+    CSET x2, EQ;
+    ADD sp, sp, #16;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        let memory_size = harness.cpu_config.memory_size as DWordType;
+        harness.assert_reg_value(29, 3);
+        harness.assert_reg_value(30, 4);
+        harness.assert_reg_value(13, 9);
+        harness.assert_reg_value(0, memory_size - 16);
+        harness.assert_reg_value(1, memory_size - 8);
+        harness.assert_reg_value(2, 1);
+        harness.assert_reg_value(SP, memory_size);
     }
 
     #[test]
-    fn test_TST() {
+    fn test_W_registers() {
         let src = r#"
 .text
-    MOV r0, #10;
-    MOV r1, #12;
-    TST r0, r1;
-    BEQ fail;
-    TST r0, #5;
-    BNE fail;
-    B end;
-fail:
-    MOV r2, #1;
-end:
+    MOV w0, #-1;
+    ADD w1, w0, #1;
+    ADDS w2, w0, #1;
+    CSET x3, CS;
+    CSET x4, EQ;
+    MOV x5, #-1;
+    ADD w6, w5, #0;
+    MOV w7, #0x80000000;
+    ASR w8, w7, #4;
+    CMP w7, #0;
+    CSET x9, MI;
+    MOV w16, #36;
+    LSL w10, w0, w16;
+    CLZ w11, w7;
+    MOV w12, #-7;
+    MOV w13, #2;
+    SDIV w14, w12, w13;
+    MOV x15, #0x100000000;
+    ADD w15, w15, w13;
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
-        harness.assert_reg_value(2, 0); // Ensure fail indicator is not set
+        harness.assert_reg_value(0, 0xFFFFFFFF);
+        harness.assert_reg_value(1, 0);
+        harness.assert_reg_value(2, 0);
+        harness.assert_reg_value(3, 1);
+        harness.assert_reg_value(4, 1);
+        harness.assert_reg_value(6, 0xFFFFFFFF);
+        harness.assert_reg_value(8, 0xF8000000);
+        harness.assert_reg_value(9, 1);
+        harness.assert_reg_value(10, 0xFFFFFFF0);
+        harness.assert_reg_value(11, 0);
+        harness.assert_reg_value(14, 0xFFFFFFFD);
+        harness.assert_reg_value(15, 2);
     }
 
     #[test]
-    fn test_LDR_STR() {
-        let src = r#"
+    fn test_register_model_invalid() {
+        let invalid = ["ADD x0, w1, x2", "SUB x0, x1, sp", "ADD sp, sp, x1, LSL #2", "MUL sp, x0, x1",
+            "LDR x0, [xzr]", "LDR x0, [x1, sp]", "PRINTR pc", "LSL w0, w1, #32", "SMULH w0, x1, x2", "MOVZ w0, #1, LSL #32"];
+        for instr in invalid {
+            let src = format!(".text\n    {};\n", instr);
+            assert!(matches!(load_from_string(CPUConfig::default(), src), Err(LoadError::AnalysisError(_))),
+                    "{} should be rejected", instr);
+        }
+    }
+
+    // A younger load can't be performed before an acquire load; a plain load has no such constraint.
+    #[test]
+    fn test_acquire_blocks_younger_loads() {
+        for (opcode, blocked) in [("LDAR", true), ("LDAPR", true), ("LDR", false)] {
+            let src = format!(r#"
 .data
     var_a: .dword 5
-    var_b: .dword 10
-    var_c: .dword 0
+    var_b: .dword 7
 .text
-    MOV r0, =var_a;
-    LDR r0, [r0];
-    MOV r1, =var_b;
-    LDR r1, [r1];
-    ADD r2, r0, r1;
-    MOV r0, =var_c;
-    STR r2, [r0];
-"#;
-        let mut harness = TestHarness::default();
-        harness.run(src);
-        harness.assert_variable_value("var_c", 15);
+    MOV x0, =var_a;
+    MOV x1, =var_b;
+    MOV x2, #100;
+    UDIV x3, x2, x2;
+    SUB x3, x3, #1;
+    ADD x4, x0, x3;
+    {} x5, [x4];
+    LDR x6, [x1];
+"#, opcode);
+            let mut harness = TestHarness::default();
+            harness.run(&src);
+            harness.assert_reg_value(5, 5);
+            harness.assert_reg_value(6, 7);
+            let acquire_blocked_cnt = harness.cpu.as_ref().unwrap().perf_counters.borrow().acquire_blocked_cnt;
+            assert_eq!(acquire_blocked_cnt > 0, blocked, "{}", opcode);
+        }
     }
 
+    // LDAR can't be performed before an older STLR is written to memory. LDAPR and a plain store don't have that constraint.
     #[test]
-    fn test_LDR() {
-        let src = r#"
+    fn test_release_store_before_acquire_load() {
+        for (store, load, blocked) in [("STLR", "LDAR", true), ("STLR", "LDAPR", false), ("STR", "LDAR", false)] {
+            let src = format!(r#"
 .data
     var_a: .dword 5
 .text
-    MOV r0, =var_a;
-    LDR r0, [r0];
+    MOV x0, =var_a;
+    MOV x1, #42;
+    MOV x3, #1;
+    UDIV x1, x1, x3;
+    {} x1, [x0];
+    {} x2, [x0];
+"#, store, load);
+            let mut harness = TestHarness::default();
+            harness.run(&src);
+            harness.assert_reg_value(2, 42);
+            harness.assert_variable_value("var_a", 42);
+            let release_blocked_cnt = harness.cpu.as_ref().unwrap().perf_counters.borrow().release_blocked_cnt;
+            assert_eq!(release_blocked_cnt > 0, blocked, "{} {}", store, load);
+        }
+    }
+
+    #[test]
+    fn test_STLR_LDAR_W_register() {
+        let src = r#"
+.data
+    var_a: .dword 0xFFFFFFFFFFFFFFFF
+    var_b: .dword 0
+.text
+    MOV x0, =var_a;
+    MOV x1, =var_b;
+    MOV w2, #0x1234;
+    STR x2, [x1];
+    STLR w2, [x0];
+    LDAR w3, [x0];
+    LDAPR x4, [x1, #0];
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
+        harness.assert_variable_value("var_a", 0xFFFFFFFF00001234);
+        harness.assert_reg_value(3, 0x1234);
+        harness.assert_reg_value(4, 0x1234);
+    }
 
-        harness.assert_reg_value(0, 5);
+    #[test]
+    fn test_acquire_release_invalid() {
+        let invalid = ["LDAR x0, [x1, #8]", "LDAPR x0, [x1, #0]!", "STLR x0, [x1], #8", "LDAR x0, [x1, x2]", "STLR x0, x1"];
+        for instr in invalid {
+            let src = format!(".text\n    {};\n", instr);
+            assert!(matches!(load_from_string(CPUConfig::default(), src), Err(LoadError::AnalysisError(_))),
+                    "{} should be rejected", instr);
+        }
     }
 
     #[test]
-    fn test_STR() {
+    fn test_LDRH_alignment_fault() {
         let src = r#"
 .data
     var_a: .dword 0
 .text
     MOV r0, =var_a;
-    MOV r1, #10;
-    STR r1, [r0];
+    ADD r0, r0, #1;
+    LDRH w1, [r0];
+    MOV r2, #1;
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
-
-        harness.assert_variable_value("var_a", 10);
+        harness.assert_fault("Alignment fault");
+        harness.assert_reg_value(2, 0);
     }
 
-    // Ensures that stores update to memory out of order even they can be performed in order.
     #[test]
-    fn test_STR_WAW() {
+    fn test_LDR_out_of_range_fault() {
         let src = r#"
-.data
-    var_a: .dword 0
 .text
-    mov r0, =var_a;
-    mov r1, #1;
-    str r1, [r0];
-    mov r2, #2;
-    str r2, [r0];
-    mov r3, #3;
-    str r3, [r0];
-    mov r4, #4;
-    str r4, [r0];
-    mov r5, #5;
-    str r5, [r0];
-    mov r6, #6;
-    str r6, [r0];
-    mov r7, #7;
-    str r7, [r0];
-    mov r8, #8;
-    str r8, [r0];
+    MOV r0, #1024;
+    LDR r1, [r0];
+    MOV r2, #1;
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
-
-        harness.assert_variable_value("var_a", 8);
+        harness.assert_fault("Out of range fault");
+        harness.assert_reg_value(2, 0);
     }
 
     #[test]
-    fn test_STR_loop() {
+    fn test_LDR_out_of_range_on_mispredicted_path() {
         let src = r#"
-.data
-    var_a: .dword 0
 .text
+    MOVZ r3, #1000, LSL #16;
     MOV r0, #100;
-    MOV r1, =var_a;
-    MOV r2, #0;
-loop:
-    ADD r2, r2, #1;
-    SUB r0, r0, #1;
-    STR r2, [r1];
-    CBNZ r0, loop;
+    MOV r1, #10;
+    SDIV r0, r0, r1;
+    SDIV r0, r0, r1;
+    CBNZ r0, skip;
+    LDR r2, [r3];
+skip:
+    MOV r4, #1;
 "#;
         let mut harness = TestHarness::default();
         harness.run(src);
-
-        harness.assert_variable_value("var_a", 100);
+        harness.assert_no_fault();
+        harness.assert_reg_value(4, 1);
     }
 
     #[test]
@@ -501,6 +1986,143 @@ _start:
         harness.assert_reg_value(2, 16);
     }
 
+    // Every return, except maybe the first, should be predicted by the return address stack.
+    #[test]
+    fn test_return_address_stack() {
+        let src = r#"
+.global _start
+.text
+_inc:
+    ADD r2, r2, #1;
+    RET;
+_start:
+    MOV r0, #10;
+loop:
+    BL _inc;
+    SUB r0, r0, #1;
+    CBNZ r0, loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 10);
+        let perf_counters = harness.cpu.as_ref().unwrap().perf_counters.borrow();
+        assert!(perf_counters.ras_hit_cnt >= 9);
+        assert_eq!(perf_counters.ras_overflow_cnt, 0);
+    }
+
+    // The target of the BX is a register, so after the first iterations it should be predicted by the BTB.
+    #[test]
+    fn test_branch_target_buffer() {
+        let src = r#"
+.text
+    MOV r0, #10;
+loop:
+    MOV r1, #4;
+    BX r1;
+    ADD r3, r3, #1;
+target:
+    ADD r2, r2, #1;
+    SUB r0, r0, #1;
+    CBNZ r0, loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 10);
+        harness.assert_reg_value(3, 0);
+        let perf_counters = harness.cpu.as_ref().unwrap().perf_counters.borrow();
+        // the BTB is updated at retire, so the BX of the second iteration can be fetched before
+        // the first BX has retired.
+        assert!(perf_counters.btb_hit_cnt >= 8);
+    }
+
+    // The target of a BLR is predicted by the BTB, while its return address is pushed on the return address stack.
+    #[test]
+    fn test_branch_target_buffer_BLR() {
+        let src = r#"
+.global _start
+.text
+_inc:
+    ADD r2, r2, #1;
+    RET;
+_start:
+    MOV r0, #10;
+    ADR r1, _inc;
+loop:
+    BLR r1;
+    SUB r0, r0, #1;
+    CBNZ r0, loop;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 10);
+        let perf_counters = harness.cpu.as_ref().unwrap().perf_counters.borrow();
+        assert_eq!(perf_counters.btb_hit_cnt + perf_counters.btb_miss_cnt, 10);
+        assert!(perf_counters.btb_hit_cnt >= 8);
+    }
+
+    // A mispredicted branch should only squash the younger instructions (including the store on
+    // the wrong path) when it executes, instead of waiting until the branch retires.
+    #[test]
+    fn test_branch_squash() {
+        let src = r#"
+.data
+    var_a: .dword 0
+.text
+    MOV r0, #10;
+    MOV r1, =var_a;
+loop:
+    ADD r2, r2, #1;
+    SUB r0, r0, #1;
+    CBNZ r0, loop;
+    MOV r3, #5;
+    STR r3, [r1];
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 10);
+        harness.assert_variable_value("var_a", 5);
+        let perf_counters = harness.cpu.as_ref().unwrap().perf_counters.borrow();
+        assert!(perf_counters.branch_miss_prediction_cnt > 0);
+        assert!(perf_counters.branch_squash_cnt >= perf_counters.branch_miss_prediction_cnt);
+        // every squash is a pipeline flush
+        assert_eq!(perf_counters.pipeline_flushes, perf_counters.branch_squash_cnt + perf_counters.memory_order_violation_cnt);
+    }
+
+    // Both ways of recovering the RAT should give the same result; only the rob walk should walk the rob.
+    #[test]
+    fn test_rename_recovery() {
+        let src = r#"
+.text
+    MOV r0, #10;
+    MOV r1, #0;
+loop:
+    ADD r1, r1, r0;
+    MUL r2, r1, r0;
+    SUB r0, r0, #1;
+    CBNZ r0, loop;
+    ADD r3, r1, r2;
+"#;
+        for rename_recovery in [RenameRecovery::Checkpoint, RenameRecovery::RobWalk] {
+            let mut cpu_config = TestHarness::new_test_cpu_config();
+            cpu_config.rename_recovery = rename_recovery;
+            let mut harness = TestHarness::new(cpu_config);
+            harness.run(src);
+
+            harness.assert_reg_value(1, 55);
+            harness.assert_reg_value(2, 55);
+            harness.assert_reg_value(3, 110);
+            let perf_counters = harness.cpu.as_ref().unwrap().perf_counters.borrow();
+            assert!(perf_counters.branch_squash_cnt > 0);
+            if rename_recovery == RenameRecovery::Checkpoint {
+                assert_eq!(perf_counters.rob_walk_cnt, 0);
+            }
+        }
+    }
+
     #[test]
     fn test_loop_CBZ() {
         let src = r#"
@@ -577,6 +2199,117 @@ _start:
         harness.assert_reg_value(2, 16);
     }
 
+    #[test]
+    fn test_BLR_BR() {
+        let src = r#"
+.global _start
+.text
+_add_numbers:
+    ADD r2, r0, r1;
+    RET;
+_start:
+    MOV r0, #5;
+    MOV r1, #10;
+    ADR r3, _add_numbers;
+    BLR r3;
+    ADD r2, r2, #1;
+    ADR r4, end;
+    BR r4;
+    MOV r2, #0;
+end:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(2, 16);
+    }
+
+    #[test]
+    fn test_BR_out_of_range_on_mispredicted_path() {
+        let src = r#"
+.text
+    MOV r0, #100;
+    MOV r1, #10;
+    SDIV r0, r0, r1;
+    SDIV r0, r0, r1;
+    CBNZ r0, skip;
+    MOV r3, #5000;
+    BR r3;
+skip:
+    MOV r4, #1;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_no_fault();
+        harness.assert_reg_value(4, 1);
+    }
+
+    #[test]
+    fn test_BR_fetch_fault() {
+        let src = r#"
+.text
+    MOV r3, #5000;
+    BR r3;
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+        harness.assert_fault("Fetch fault");
+    }
+
+    // Counts the set bits of the lowest byte.
+    #[test]
+    fn test_TBZ_TBNZ_loop() {
+        let src = r#"
+.text
+    MOV r0, #0xB5;
+    MOV r1, #0;
+    MOV r2, #8;
+loop:
+    TBZ r0, #0, skip;
+    ADD r1, r1, #1;
+skip:
+    LSR r0, r0, #1;
+    SUB r2, r2, #1;
+    CBNZ r2, loop;
+    MOV r3, #0;
+    LDR r4, =0x8000000000000000;
+    TBNZ r4, #63, end;
+    MOV r3, #1;
+end:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(1, 5);
+        harness.assert_reg_value(3, 0);
+    }
+
+    #[test]
+    fn test_TBZ_TBNZ_W() {
+        let src = r#"
+.text
+    MOV r0, #2;
+    MOV r1, #0;
+    TBZ w0, #0, skip;
+    MOV r1, #1;
+skip:
+    LDR w2, =0x80000000;
+    TBNZ w2, #31, end;
+    MOV r1, #2;
+end:
+"#;
+        let mut harness = TestHarness::default();
+        harness.run(src);
+
+        harness.assert_reg_value(1, 0);
+
+        let src = ".text\n    TBZ w0, #32, end;\nend:\n".to_string();
+        match load_from_string(CPUConfig::default(), src) {
+            Err(LoadError::AnalysisError(msg_vec)) => assert!(msg_vec[0].contains("out of range [0, 31]")),
+            _ => panic!("TBZ w0, #32 should be rejected"),
+        }
+    }
+
     #[test]
     fn test_binary_value() {
         let src = r#"
@@ -630,7 +2363,10 @@ _start:
 
     impl TestHarness {
         fn default() -> TestHarness {
-            let cpu_config = Self::new_test_cpu_config();
+            Self::new(Self::new_test_cpu_config())
+        }
+
+        fn new(cpu_config: CPUConfig) -> TestHarness {
             TestHarness {
                 program: None,
                 cpu: Some(CPU::new(&cpu_config.clone())),
@@ -692,20 +2428,31 @@ _start:
             }
         }
 
+        fn assert_fault(&self, expected: &str) {
+            let cpu = self.cpu.as_ref().expect("CPU is not initialized");
+            match &cpu.backend.fault {
+                Some(fault) => assert!(fault.contains(expected), "Unexpected fault: {}", fault),
+                None => panic!("Expected fault '{}'", expected),
+            }
+        }
+
+        fn assert_no_fault(&self) {
+            let cpu = self.cpu.as_ref().expect("CPU is not initialized");
+            assert_eq!(cpu.backend.fault, None);
+        }
+
         fn assert_variable_value(&self, name: &str, value: DWordType) {
             if let Some(ref cpu) = self.cpu {
                 let program = self.program.as_ref().expect("Program not initialized");
                 let data_item = program.data_items.get(name).expect("Data item not found");
                 let offset = data_item.offset;
+                let size = data_item.data_type.size();
                 let memory_subsystem = cpu.memory_subsystem.borrow();
-                match memory_subsystem.memory.get(offset as usize) {
-                    Some(&actual_value) => {
-                        assert_eq!(actual_value, value, "Variable '{}' does not have the expected value", name);
-                    }
-                    None => {
-                        panic!("Memory offset {} is invalid", offset);
-                    }
+                if offset as usize + size as usize > memory_subsystem.memory.len() {
+                    panic!("Memory offset {} is invalid", offset);
                 }
+                let actual_value = read(&memory_subsystem.memory, offset, size);
+                assert_eq!(actual_value, value, "Variable '{}' does not have the expected value", name);
             } else {
                 panic!("CPU is not initialized");
             }
@@ -0,0 +1,401 @@
+use crate::cpu::{BranchPredictorConfig, BranchPredictorType};
+
+/// A branch predictor predicts the direction of conditional branches.
+///
+/// The frontend asks for a prediction when a branch is fetched and the backend trains
+/// the predictor when the branch retires. Predictors that use a global history, update
+/// the history speculatively at fetch. On a pipeline flush, the speculative history is
+/// restored from the history of the retired branches.
+pub(crate) trait BranchPredictor {
+    fn name(&self) -> &'static str;
+
+    // Predicts if the branch at the given pc with the given target is taken.
+    fn predict(&mut self, pc: usize, target: usize) -> bool;
+
+    // Trains the predictor with the actual outcome of the branch at the given pc.
+    fn update(&mut self, pc: usize, taken: bool);
+
+    // Discards the speculative state.
+    fn flush(&mut self) {}
+
+    // Adds the outcome of an in flight branch to the speculative state. Used to rebuild the
+    // speculative state after a flush when not all branches on the wrong path were discarded.
+    fn speculate(&mut self, _taken: bool) {}
+}
+
+pub(crate) fn create_branch_predictor(config: &BranchPredictorConfig) -> Box<dyn BranchPredictor> {
+    match config.kind {
+        BranchPredictorType::Static => Box::new(StaticPredictor {}),
+        BranchPredictorType::Bimodal => Box::new(BimodalPredictor::new(config.table_size)),
+        BranchPredictorType::Gshare => Box::new(GSharePredictor::new(config.table_size, config.history_bits)),
+        BranchPredictorType::Tage => Box::new(TagePredictor::new(config.table_size, config.history_bits)),
+    }
+}
+
+// A 2-bit saturating counter; values 2 and 3 predict taken.
+fn counter_update(counter: u8, taken: bool) -> u8 {
+    if taken {
+        if counter < 3 { counter + 1 } else { counter }
+    } else if counter > 0 {
+        counter - 1
+    } else {
+        counter
+    }
+}
+
+fn counter_taken(counter: u8) -> bool {
+    counter >= 2
+}
+
+fn history_mask(bits: u8) -> u64 {
+    if bits >= 64 { u64::MAX } else { (1u64 << bits) - 1 }
+}
+
+/// The global branch history; the most recent outcome is the least significant bit.
+struct GlobalHistory {
+    // includes the predicted outcome of the branches in flight; used for predictions.
+    speculative: u64,
+    // only includes the retired branches; used for training.
+    retired: u64,
+    mask: u64,
+}
+
+impl GlobalHistory {
+    fn new(bits: u8) -> Self {
+        Self { speculative: 0, retired: 0, mask: history_mask(bits) }
+    }
+
+    fn speculate(&mut self, taken: bool) {
+        self.speculative = ((self.speculative << 1) | taken as u64) & self.mask;
+    }
+
+    fn retire(&mut self, taken: bool) {
+        self.retired = ((self.retired << 1) | taken as u64) & self.mask;
+    }
+
+    fn flush(&mut self) {
+        self.speculative = self.retired;
+    }
+}
+
+/// A static predictor that speculates that backwards branches are taken.
+pub(crate) struct StaticPredictor {}
+
+impl BranchPredictor for StaticPredictor {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    fn predict(&mut self, pc: usize, target: usize) -> bool {
+        target < pc
+    }
+
+    fn update(&mut self, _pc: usize, _taken: bool) {}
+}
+
+/// A table of 2-bit saturating counters indexed by the pc.
+pub(crate) struct BimodalPredictor {
+    counters: Vec<u8>,
+}
+
+impl BimodalPredictor {
+    pub(crate) fn new(table_size: u16) -> Self {
+        assert!(table_size > 0, "BimodalPredictor: table_size must be larger than 0");
+
+        // weakly not taken
+        Self { counters: vec![1; table_size as usize] }
+    }
+
+    fn index(&self, pc: usize) -> usize {
+        pc % self.counters.len()
+    }
+}
+
+impl BranchPredictor for BimodalPredictor {
+    fn name(&self) -> &'static str {
+        "bimodal"
+    }
+
+    fn predict(&mut self, pc: usize, _target: usize) -> bool {
+        counter_taken(self.counters[self.index(pc)])
+    }
+
+    fn update(&mut self, pc: usize, taken: bool) {
+        let index = self.index(pc);
+        self.counters[index] = counter_update(self.counters[index], taken);
+    }
+}
+
+/// A table of 2-bit saturating counters indexed by the pc xor-ed with the global history.
+pub(crate) struct GSharePredictor {
+    counters: Vec<u8>,
+    history: GlobalHistory,
+}
+
+impl GSharePredictor {
+    pub(crate) fn new(table_size: u16, history_bits: u8) -> Self {
+        assert!(table_size > 0, "GSharePredictor: table_size must be larger than 0");
+        assert!(history_bits <= 64, "GSharePredictor: history_bits must not be larger than 64");
+
+        Self { counters: vec![1; table_size as usize], history: GlobalHistory::new(history_bits) }
+    }
+
+    fn index(&self, pc: usize, history: u64) -> usize {
+        ((pc as u64 ^ history) % self.counters.len() as u64) as usize
+    }
+}
+
+impl BranchPredictor for GSharePredictor {
+    fn name(&self) -> &'static str {
+        "gshare"
+    }
+
+    fn predict(&mut self, pc: usize, _target: usize) -> bool {
+        let taken = counter_taken(self.counters[self.index(pc, self.history.speculative)]);
+        self.history.speculate(taken);
+        taken
+    }
+
+    fn update(&mut self, pc: usize, taken: bool) {
+        let index = self.index(pc, self.history.retired);
+        self.counters[index] = counter_update(self.counters[index], taken);
+        self.history.retire(taken);
+    }
+
+    fn flush(&mut self) {
+        self.history.flush();
+    }
+
+    fn speculate(&mut self, taken: bool) {
+        self.history.speculate(taken);
+    }
+}
+
+const TAGE_TABLE_CNT: usize = 4;
+const TAGE_TAG_BITS: u8 = 8;
+// The counter of a tagged entry is a 3-bit counter; values 4 and up predict taken.
+const TAGE_COUNTER_MAX: u8 = 7;
+
+#[derive(Clone, Copy)]
+struct TageEntry {
+    tag: u16,
+    counter: u8,
+    useful: u8,
+    valid: bool,
+}
+
+/// A TAGE-like predictor: a bimodal base predictor plus a number of tagged tables that
+/// are indexed with geometrically increasing lengths of the global history. The
+/// prediction comes from the matching table with the longest history.
+pub(crate) struct TagePredictor {
+    base: BimodalPredictor,
+    tables: Vec<Vec<TageEntry>>,
+    history_lengths: [u8; TAGE_TABLE_CNT],
+    history: GlobalHistory,
+}
+
+impl TagePredictor {
+    pub(crate) fn new(table_size: u16, history_bits: u8) -> Self {
+        assert!(table_size > 0, "TagePredictor: table_size must be larger than 0");
+        assert!(history_bits <= 64, "TagePredictor: history_bits must not be larger than 64");
+
+        let empty = TageEntry { tag: 0, counter: 0, useful: 0, valid: false };
+        let tables = vec![vec![empty; table_size as usize]; TAGE_TABLE_CNT];
+
+        // geometric history lengths ending at history_bits, e.g. 8, 16, 32, 64.
+        let mut history_lengths = [0u8; TAGE_TABLE_CNT];
+        for (k, length) in history_lengths.iter_mut().enumerate() {
+            let shift = TAGE_TABLE_CNT - 1 - k;
+            *length = (history_bits >> shift).max(1);
+        }
+
+        Self {
+            base: BimodalPredictor::new(table_size),
+            tables,
+            history_lengths,
+            history: GlobalHistory::new(history_bits),
+        }
+    }
+
+    // Folds the most recent 'length' bits of the history into 'bits' bits.
+    fn fold(history: u64, length: u8, bits: u8) -> u64 {
+        let mut history = history & history_mask(length);
+        let mut folded = 0;
+        while history != 0 {
+            folded ^= history & history_mask(bits);
+            history >>= bits;
+        }
+        folded
+    }
+
+    fn index(&self, table: usize, pc: usize, history: u64) -> usize {
+        let size = self.tables[table].len() as u64;
+        let bits = (64 - size.leading_zeros()) as u8;
+        ((pc as u64 ^ Self::fold(history, self.history_lengths[table], bits)) % size) as usize
+    }
+
+    fn tag(&self, table: usize, pc: usize, history: u64) -> u16 {
+        let folded = Self::fold(history, self.history_lengths[table], TAGE_TAG_BITS);
+        ((pc as u64 ^ (folded << 1) ^ table as u64) & history_mask(TAGE_TAG_BITS)) as u16
+    }
+
+    // Returns the longest matching table (provider) and the next longest one (alternate).
+    fn lookup(&self, pc: usize, history: u64) -> (Option<usize>, Option<usize>) {
+        let mut provider = None;
+        let mut alternate = None;
+        for table in (0..TAGE_TABLE_CNT).rev() {
+            let entry = &self.tables[table][self.index(table, pc, history)];
+            if entry.valid && entry.tag == self.tag(table, pc, history) {
+                if provider.is_none() {
+                    provider = Some(table);
+                } else {
+                    alternate = Some(table);
+                    break;
+                }
+            }
+        }
+        (provider, alternate)
+    }
+
+    fn table_prediction(&self, table: Option<usize>, pc: usize, history: u64) -> bool {
+        match table {
+            Some(table) => self.tables[table][self.index(table, pc, history)].counter > TAGE_COUNTER_MAX / 2,
+            None => counter_taken(self.base.counters[self.base.index(pc)]),
+        }
+    }
+}
+
+impl BranchPredictor for TagePredictor {
+    fn name(&self) -> &'static str {
+        "tage"
+    }
+
+    fn predict(&mut self, pc: usize, _target: usize) -> bool {
+        let history = self.history.speculative;
+        let (provider, _) = self.lookup(pc, history);
+        let taken = self.table_prediction(provider, pc, history);
+        self.history.speculate(taken);
+        taken
+    }
+
+    fn update(&mut self, pc: usize, taken: bool) {
+        let history = self.history.retired;
+        let (provider, alternate) = self.lookup(pc, history);
+        let prediction = self.table_prediction(provider, pc, history);
+
+        match provider {
+            Some(table) => {
+                let alternate_prediction = self.table_prediction(alternate, pc, history);
+                let index = self.index(table, pc, history);
+                let entry = &mut self.tables[table][index];
+                if taken {
+                    entry.counter = (entry.counter + 1).min(TAGE_COUNTER_MAX);
+                } else {
+                    entry.counter = entry.counter.saturating_sub(1);
+                }
+
+                // the entry is useful if it provided a different (and correct) prediction than the alternate
+                if prediction != alternate_prediction {
+                    if prediction == taken {
+                        entry.useful = (entry.useful + 1).min(3);
+                    } else {
+                        entry.useful = entry.useful.saturating_sub(1);
+                    }
+                }
+            }
+            None => self.base.update(pc, taken),
+        }
+
+        if prediction != taken {
+            // allocate an entry in a table with a longer history than the provider
+            let first = provider.map_or(0, |table| table + 1);
+            let mut allocated = false;
+            for table in first..TAGE_TABLE_CNT {
+                let index = self.index(table, pc, history);
+                let tag = self.tag(table, pc, history);
+                let entry = &mut self.tables[table][index];
+                if !entry.valid || entry.useful == 0 {
+                    // weakly in the direction of the outcome
+                    let counter = if taken { TAGE_COUNTER_MAX / 2 + 1 } else { TAGE_COUNTER_MAX / 2 };
+                    *entry = TageEntry { tag, counter, useful: 0, valid: true };
+                    allocated = true;
+                    break;
+                }
+            }
+
+            if !allocated {
+                // age the entries so that eventually a slot becomes available
+                for table in first..TAGE_TABLE_CNT {
+                    let index = self.index(table, pc, history);
+                    let entry = &mut self.tables[table][index];
+                    entry.useful = entry.useful.saturating_sub(1);
+                }
+            }
+        }
+
+        self.history.retire(taken);
+    }
+
+    fn flush(&mut self) {
+        self.history.flush();
+    }
+
+    fn speculate(&mut self, taken: bool) {
+        self.history.speculate(taken);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trains the predictor with a pattern for a single branch and returns the number of
+    // correct predictions in the last round. Like the pipeline, a misprediction flushes
+    // the speculative state.
+    fn train(predictor: &mut dyn BranchPredictor, pattern: &[bool]) -> usize {
+        let pc = 10;
+        let mut correct = 0;
+        for round in 0..50 {
+            for &taken in pattern {
+                let predicted = predictor.predict(pc, 0);
+                predictor.update(pc, taken);
+                if predicted != taken {
+                    predictor.flush();
+                } else if round == 49 {
+                    correct += 1;
+                }
+            }
+        }
+        correct
+    }
+
+    #[test]
+    #[should_panic(expected = "table_size must be larger than 0")]
+    fn test_gshare_empty_table() {
+        GSharePredictor::new(0, 12);
+    }
+
+    #[test]
+    fn test_bimodal_biased() {
+        let mut predictor = BimodalPredictor::new(64);
+        assert_eq!(train(&mut predictor, &[true, true, true, false]), 3);
+    }
+
+    #[test]
+    fn test_gshare_alternating() {
+        let mut bimodal = BimodalPredictor::new(64);
+        let mut gshare = GSharePredictor::new(64, 4);
+
+        let pattern = [true, false, true, false];
+        assert!(train(&mut bimodal, &pattern) < pattern.len());
+        assert_eq!(train(&mut gshare, &pattern), pattern.len());
+    }
+
+    #[test]
+    fn test_tage_long_pattern() {
+        let mut tage = TagePredictor::new(256, 16);
+
+        let pattern = [true, true, true, true, true, false, true, false, false];
+        assert_eq!(train(&mut tage, &pattern), pattern.len());
+    }
+}
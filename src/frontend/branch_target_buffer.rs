@@ -0,0 +1,133 @@
+use crate::cpu::CPUConfig;
+
+#[derive(Clone, Copy)]
+struct BTBEntry {
+    pc: usize,
+    target: usize,
+    // the 'time' of the last use; used to find the least recently used entry of a set.
+    last_used: u64,
+    valid: bool,
+}
+
+/// The branch target buffer (BTB) predicts the target of branches with a register
+/// target like BX. It is a set associative cache indexed by the pc of the branch.
+///
+/// The frontend does a lookup when the branch is fetched and the backend updates
+/// the BTB with the actual target when the branch retires.
+pub(crate) struct BTB {
+    entries: Vec<BTBEntry>,
+    set_count: usize,
+    associativity: usize,
+    clock: u64,
+}
+
+impl BTB {
+    pub(crate) fn new(cpu_config: &CPUConfig) -> BTB {
+        let size = cpu_config.btb_size as usize;
+        let associativity = cpu_config.btb_associativity as usize;
+        assert!(associativity > 0, "BTB: associativity must be larger than 0");
+        assert!(size.is_multiple_of(associativity), "BTB: size must be a multiple of the associativity");
+
+        let empty = BTBEntry { pc: 0, target: 0, last_used: 0, valid: false };
+        BTB {
+            entries: vec![empty; size],
+            set_count: size / associativity,
+            associativity,
+            clock: 0,
+        }
+    }
+
+    fn set_range(&self, pc: usize) -> std::ops::Range<usize> {
+        let first = (pc % self.set_count) * self.associativity;
+        first..first + self.associativity
+    }
+
+    // Returns the predicted target of the branch at the given pc or None if the branch
+    // isn't in the BTB.
+    pub(crate) fn lookup(&mut self, pc: usize) -> Option<usize> {
+        if self.set_count == 0 {
+            return None;
+        }
+
+        self.clock += 1;
+        for index in self.set_range(pc) {
+            let entry = &mut self.entries[index];
+            if entry.valid && entry.pc == pc {
+                entry.last_used = self.clock;
+                return Some(entry.target);
+            }
+        }
+        None
+    }
+
+    // Records the actual target of the branch at the given pc. If the branch isn't in the
+    // BTB, an empty entry or else the least recently used entry of the set is replaced.
+    pub(crate) fn update(&mut self, pc: usize, target: usize) {
+        if self.set_count == 0 {
+            return;
+        }
+
+        self.clock += 1;
+        let mut victim = None;
+        for index in self.set_range(pc) {
+            let entry = &self.entries[index];
+            if entry.valid && entry.pc == pc {
+                victim = Some(index);
+                break;
+            }
+
+            victim = match victim {
+                None => Some(index),
+                Some(v) => {
+                    let v_entry = &self.entries[v];
+                    if v_entry.valid && (!entry.valid || entry.last_used < v_entry.last_used) {
+                        Some(index)
+                    } else {
+                        Some(v)
+                    }
+                }
+            };
+        }
+
+        let entry = &mut self.entries[victim.unwrap()];
+        *entry = BTBEntry { pc, target, last_used: self.clock, valid: true };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_btb(size: u16, associativity: u16) -> BTB {
+        let cpu_config = CPUConfig {
+            btb_size: size,
+            btb_associativity: associativity,
+            ..CPUConfig::default()
+        };
+        BTB::new(&cpu_config)
+    }
+
+    #[test]
+    fn test_lookup_update() {
+        let mut btb = new_btb(8, 2);
+        assert_eq!(btb.lookup(10), None);
+        btb.update(10, 100);
+        assert_eq!(btb.lookup(10), Some(100));
+        btb.update(10, 200);
+        assert_eq!(btb.lookup(10), Some(200));
+    }
+
+    #[test]
+    fn test_lru_replacement() {
+        // a single set with 2 ways
+        let mut btb = new_btb(2, 2);
+        btb.update(1, 100);
+        btb.update(2, 200);
+        // makes 2 the least recently used
+        btb.lookup(1);
+        btb.update(3, 300);
+        assert_eq!(btb.lookup(1), Some(100));
+        assert_eq!(btb.lookup(2), None);
+        assert_eq!(btb.lookup(3), Some(300));
+    }
+}
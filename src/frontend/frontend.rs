@@ -2,10 +2,19 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use crate::cpu::{ArgRegFile, CPUConfig, PC, PerfCounters, Trace};
-use crate::instructions::instructions::{Branch, BranchTarget, DWordType, EXIT, Instr, InstrQueue, Opcode, Program};
+use crate::frontend::branch_predictor::BranchPredictor;
+use crate::frontend::branch_target_buffer::BTB;
+use crate::frontend::return_address_stack::ReturnAddressStack;
+use crate::instructions::instructions::{Branch, BranchTarget, DWordType, EXIT, Instr, InstrQueue, NOP, Opcode, Program};
 
 pub(crate) struct FrontendControl {
     pub(crate) halted: bool,
+    // set when the frontend has fetched the EXIT. It is cleared on a pipeline flush
+    // because the EXIT could have been fetched on a wrong path.
+    pub(crate) exit: bool,
+    // set when the frontend has fetched a pc outside of the program. It is cleared on a pipeline
+    // flush because a register target branch on a wrong path can steer the frontend to a garbage pc.
+    pub(crate) fetch_fault: bool,
 }
 
 pub(crate) struct Frontend {
@@ -14,18 +23,24 @@ pub(crate) struct Frontend {
     frontend_control: Rc<RefCell<FrontendControl>>,
     program_option: Option<Rc<Program>>,
     trace: Trace,
-    exit: bool,
     perf_counters: Rc<RefCell<PerfCounters>>,
     arch_reg_file: Rc<RefCell<ArgRegFile>>,
+    branch_predictor: Rc<RefCell<Box<dyn BranchPredictor>>>,
+    return_address_stack: Rc<RefCell<ReturnAddressStack>>,
+    btb: Rc<RefCell<BTB>>,
 }
 
 impl Frontend {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         cpu_config: &CPUConfig,
         instr_queue: &Rc<RefCell<InstrQueue>>,
         frontend_control: &Rc<RefCell<FrontendControl>>,
         perf_counters: &Rc<RefCell<PerfCounters>>,
         arch_reg_file: &Rc<RefCell<ArgRegFile>>,
+        branch_predictor: &Rc<RefCell<Box<dyn BranchPredictor>>>,
+        return_address_stack: &Rc<RefCell<ReturnAddressStack>>,
+        btb: &Rc<RefCell<BTB>>,
     ) -> Frontend {
         Frontend {
             instr_queue: Rc::clone(instr_queue),
@@ -33,9 +48,11 @@ impl Frontend {
             program_option: None,
             trace: cpu_config.trace.clone(),
             frontend_control: Rc::clone(frontend_control),
-            exit: false,
             perf_counters: Rc::clone(perf_counters),
             arch_reg_file: Rc::clone(arch_reg_file),
+            branch_predictor: Rc::clone(branch_predictor),
+            return_address_stack: Rc::clone(return_address_stack),
+            btb: Rc::clone(btb),
         }
     }
 
@@ -49,7 +66,7 @@ impl Frontend {
             None => return,
             Some(program) => {
                 let mut instr_queue = self.instr_queue.borrow_mut();
-                let frontend_control = self.frontend_control.borrow_mut();
+                let mut frontend_control = self.frontend_control.borrow_mut();
                 let mut perf_counters = self.perf_counters.borrow_mut();
                 let mut arch_reg_file = self.arch_reg_file.borrow_mut();
 
@@ -58,7 +75,7 @@ impl Frontend {
                 }
 
                 for _ in 0..self.n_wide {
-                    if self.exit {
+                    if frontend_control.exit || frontend_control.fetch_fault {
                         return;
                     }
 
@@ -72,6 +89,24 @@ impl Frontend {
                     // B foobar
 
                     let pc = arch_reg_file.get_value(PC) as usize;
+                    if pc > program.code.len() {
+                        // A NOP is fetched instead that faults when it retires. The fetching stops
+                        // until the pipeline is flushed.
+                        if self.trace.decode {
+                            println!("Frontend: pc: {}  outside of the program", pc);
+                        }
+
+                        frontend_control.fetch_fault = true;
+                        let tail_index = instr_queue.tail_index();
+                        let slot = instr_queue.get_mut(tail_index);
+                        slot.instr = Rc::new(NOP);
+                        slot.pc = pc;
+                        slot.branch_target_predicted = 0;
+                        slot.fetch_fault = true;
+                        instr_queue.tail_bump();
+                        return;
+                    }
+
                     let instr = if program.code.len() == pc {
                         // at the end of the program
                         Rc::new(EXIT)
@@ -85,7 +120,7 @@ impl Frontend {
 
                     if let Instr::Synchronization (synchronization ) = instr.as_ref() {
                         if synchronization.opcode == Opcode::EXIT {
-                            self.exit = true;
+                            frontend_control.exit = true;
                         }
                     }
 
@@ -94,7 +129,7 @@ impl Frontend {
 
                     let pc_value_next = match instr.as_ref() {
                         Instr::Branch (branch ) => {
-                            slot.branch_target_predicted = Self::predict(pc, branch);
+                            slot.branch_target_predicted = self.predict(pc, branch);
                             //println!("Frontend branch predicted={}", slot.branch_target_predicted);
                             slot.branch_target_predicted
                         }
@@ -104,6 +139,7 @@ impl Frontend {
 
                     slot.instr = instr;
                     slot.pc = pc;
+                    slot.fetch_fault = false;
                     instr_queue.tail_bump();
                     perf_counters.decode_cnt += 1;
                 }
@@ -111,39 +147,57 @@ impl Frontend {
         }
     }
 
-    // A static branch predictor that will speculate that backwards branches are taken.
-    // In the future better branch predictors can be added.
-    fn predict(ip: usize, branch: &Branch) -> usize {
-        let branch_target = match branch.opcode {
+    pub(crate) fn branch_predictor_name(&self) -> &'static str {
+        self.branch_predictor.borrow().name()
+    }
+
+    // Predicts the next pc after the branch. Unconditional branches with an immediate target are
+    // always predicted correctly, the direction of conditional branches is predicted by the
+    // branch predictor, the target of returns by the return address stack and the target of
+    // the other register target branches by the branch target buffer.
+    fn predict(&self, ip: usize, branch: &Branch) -> usize {
+        if branch.is_return() {
+            if let Some(target) = self.return_address_stack.borrow_mut().pop() {
+                return target;
+            }
+        }
+
+        if let BranchTarget::Register { .. } = branch.target {
+            // a BLR is a call; so the return address is pushed like for a BL
+            if branch.link_bit {
+                self.return_address_stack.borrow_mut().push(ip + 1);
+            }
+
+            // if the target is unknown, just continue with the next instruction
+            return self.btb.borrow_mut().lookup(ip).unwrap_or(ip + 1);
+        }
+
+        match branch.opcode {
             Opcode::B |
             Opcode::BL => if let BranchTarget::Immediate { offset } = branch.target {
+                if branch.link_bit {
+                    self.return_address_stack.borrow_mut().push(ip + 1);
+                }
+
                 // unconditional branches can be predicted with 100% certainty
-                return offset as usize;
+                offset as usize
             } else {
                 panic!();
             }
-            Opcode::RET => 0,
-            Opcode::BX => 0,
             Opcode::CBNZ |
             Opcode::CBZ |
-            Opcode::BNE |
-            Opcode::BLE |
-            Opcode::BLT |
-            Opcode::BGE |
-            Opcode::BGT |
-            Opcode::BEQ => if let BranchTarget::Immediate { offset } = branch.target {
-                offset as usize
+            Opcode::TBNZ |
+            Opcode::TBZ |
+            Opcode::BCOND => if let BranchTarget::Immediate { offset } = branch.target {
+                if !branch.is_conditional() || self.branch_predictor.borrow_mut().predict(ip, offset as usize) {
+                    offset as usize
+                } else {
+                    ip + 1
+                }
             } else {
                 panic!();
             },
             _ => unreachable!(),
-        };
-
-        if (branch_target as usize) < ip {
-            // backwards branches are always taken
-            branch_target as usize
-        } else {
-            ip + 1
         }
     }
 }
@@ -1 +1,4 @@
-pub mod frontend;
\ No newline at end of file
+pub mod frontend;
+pub(crate) mod branch_predictor;
+pub(crate) mod return_address_stack;
+pub(crate) mod branch_target_buffer;
\ No newline at end of file
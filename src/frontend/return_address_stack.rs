@@ -0,0 +1,127 @@
+/// A circular stack of return addresses.
+#[derive(Clone)]
+struct AddressStack {
+    entries: Vec<usize>,
+    // the index of the next free entry
+    top: usize,
+    size: usize,
+}
+
+impl AddressStack {
+    fn new(depth: u16) -> Self {
+        Self { entries: vec![0; depth as usize], top: 0, size: 0 }
+    }
+
+    // Pushes the address. Returns true if the stack overflowed; in that case the
+    // oldest address is overwritten.
+    fn push(&mut self, address: usize) -> bool {
+        let depth = self.entries.len();
+        if depth == 0 {
+            return true;
+        }
+
+        self.entries[self.top] = address;
+        self.top = (self.top + 1) % depth;
+        if self.size == depth {
+            true
+        } else {
+            self.size += 1;
+            false
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        if self.size == 0 {
+            return None;
+        }
+
+        let depth = self.entries.len();
+        self.top = (self.top + depth - 1) % depth;
+        self.size -= 1;
+        Some(self.entries[self.top])
+    }
+}
+
+/// The return address stack (RAS) predicts the target of function returns.
+///
+/// The frontend pushes the return address when a BL is fetched and pops it when a
+/// RET (or BX lr) is fetched. Because the frontend runs ahead on a speculative path,
+/// a second stack is maintained that only contains the effects of the retired
+/// branches. On a pipeline flush the speculative stack is repaired from that one.
+pub(crate) struct ReturnAddressStack {
+    speculative: AddressStack,
+    retired: AddressStack,
+}
+
+impl ReturnAddressStack {
+    pub(crate) fn new(depth: u16) -> Self {
+        Self {
+            speculative: AddressStack::new(depth),
+            retired: AddressStack::new(depth),
+        }
+    }
+
+    pub(crate) fn push(&mut self, return_address: usize) {
+        self.speculative.push(return_address);
+    }
+
+    // Returns the predicted return address or None if the stack is empty.
+    pub(crate) fn pop(&mut self) -> Option<usize> {
+        self.speculative.pop()
+    }
+
+    // Called when a BL retires. Returns true if the stack overflowed.
+    pub(crate) fn retire_push(&mut self, return_address: usize) -> bool {
+        self.retired.push(return_address)
+    }
+
+    // Called when a return retires.
+    pub(crate) fn retire_pop(&mut self) {
+        self.retired.pop();
+    }
+
+    pub(crate) fn flush(&mut self) {
+        self.speculative = self.retired.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_pop() {
+        let mut ras = ReturnAddressStack::new(2);
+        ras.push(10);
+        ras.push(20);
+        assert_eq!(ras.pop(), Some(20));
+        assert_eq!(ras.pop(), Some(10));
+        assert_eq!(ras.pop(), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut ras = ReturnAddressStack::new(2);
+        assert!(!ras.retire_push(10));
+        assert!(!ras.retire_push(20));
+        assert!(ras.retire_push(30));
+        ras.flush();
+        assert_eq!(ras.pop(), Some(30));
+        assert_eq!(ras.pop(), Some(20));
+        assert_eq!(ras.pop(), None);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut ras = ReturnAddressStack::new(4);
+        ras.push(10);
+        ras.retire_push(10);
+        // wrong path
+        ras.pop();
+        ras.push(30);
+        ras.push(40);
+        ras.flush();
+        assert_eq!(ras.pop(), Some(10));
+        assert_eq!(ras.pop(), None);
+    }
+}
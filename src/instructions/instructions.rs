@@ -3,7 +3,9 @@ use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 
-use crate::cpu::{CPSR, SP};
+use serde::Deserialize;
+
+use crate::cpu::{CPSR, SP, UnitType, XZR};
 use crate::cpu::FP;
 use crate::cpu::LR;
 use crate::cpu::PC;
@@ -23,6 +25,7 @@ impl Display for RegisterTypeDisplay {
             SP => write!(f, "SP"),
             PC => write!(f, "PC"),
             CPSR => write!(f, "CPSR"),
+            XZR => write!(f, "XZR"),
             _ => write!(f, "R{}", self.register),
         }
     }
@@ -40,41 +43,95 @@ impl Display for SourceLocation {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
 pub enum Opcode {
     ADD,
+    ADDS,
+    ADC,
+    ADCS,
     SUB,
+    SUBS,
+    SBC,
+    SBCS,
     RSB,
     MUL,
+    MADD,
+    MSUB,
+    MNEG,
+    SMULL,
+    UMULL,
+    SMULH,
+    UMULH,
     SDIV,
+    UDIV,
     ADR,
+    ADRP,
     LDR,
+    LDRB,
+    LDRH,
+    LDRSB,
+    LDRSH,
+    LDRSW,
+    LDP,
+    LDAR,
+    LDAPR,
     STR,
+    STRB,
+    STRH,
+    STP,
+    STLR,
     NOP,
     PRINTR,
     MOV,
+    MOVZ,
+    MOVN,
+    MOVK,
     B,
     BX,
+    BR,
     BL,
+    BLR,
     RET,
     CBZ,
     CBNZ,
+    TBZ,
+    TBNZ,
     // Acts like a poison pill. It isn't a public instruction.
     EXIT,
     NEG,
     AND,
+    ANDS,
     ORR,
     EOR,
     MVN,
+    UBFX,
+    SBFX,
+    BFI,
+    BFXIL,
+    CLZ,
+    CLS,
+    RBIT,
+    REV,
+    REV16,
+    REV32,
+    LSL,
+    LSR,
+    ASR,
+    ROR,
+    CSEL,
+    CSINC,
+    CSINV,
+    CSNEG,
+    CSET,
+    CSETM,
+    CINC,
     CMP,
+    CCMP,
+    CCMN,
     TST,
     TEQ,
-    BEQ,
-    BNE,
-    BLE,
-    BLT,
-    BGE,
-    BGT,
+    // the conditional branch B.<cond>; the condition is stored in the branch.
+    BCOND,
     DSB,
 }
 
@@ -84,38 +141,148 @@ pub(crate) fn mnemonic(opcode: Opcode) -> &'static str {
         Opcode::SUB => "SUB",
         Opcode::RSB => "RSB",
         Opcode::MUL => "MUL",
+        Opcode::MADD => "MADD",
+        Opcode::MSUB => "MSUB",
+        Opcode::MNEG => "MNEG",
+        Opcode::SMULL => "SMULL",
+        Opcode::UMULL => "UMULL",
+        Opcode::SMULH => "SMULH",
+        Opcode::UMULH => "UMULH",
         Opcode::SDIV => "SDIV",
+        Opcode::UDIV => "UDIV",
         Opcode::NEG => "NEG",
         Opcode::ADR => "ADR",
+        Opcode::ADRP => "ADRP",
         Opcode::LDR => "LDR",
+        Opcode::LDRB => "LDRB",
+        Opcode::LDRH => "LDRH",
+        Opcode::LDRSB => "LDRSB",
+        Opcode::LDRSH => "LDRSH",
+        Opcode::LDRSW => "LDRSW",
+        Opcode::LDP => "LDP",
+        Opcode::LDAR => "LDAR",
+        Opcode::LDAPR => "LDAPR",
         Opcode::STR => "STR",
+        Opcode::STRB => "STRB",
+        Opcode::STRH => "STRH",
+        Opcode::STP => "STP",
+        Opcode::STLR => "STLR",
         Opcode::NOP => "NOP",
         Opcode::PRINTR => "PRINTR",
         Opcode::MOV => "MOV",
+        Opcode::MOVZ => "MOVZ",
+        Opcode::MOVN => "MOVN",
+        Opcode::MOVK => "MOVK",
         Opcode::B => "B",
         Opcode::RET => "RET",
         Opcode::BX => "BX",
+        Opcode::BR => "BR",
+        Opcode::BLR => "BLR",
         Opcode::BL => "BL",
         Opcode::CBZ => "CBZ",
         Opcode::CBNZ => "CBNZ",
+        Opcode::TBZ => "TBZ",
+        Opcode::TBNZ => "TBNZ",
         Opcode::AND => "AND",
+        Opcode::ANDS => "ANDS",
+        Opcode::ADDS => "ADDS",
+        Opcode::ADC => "ADC",
+        Opcode::ADCS => "ADCS",
+        Opcode::SUBS => "SUBS",
+        Opcode::SBC => "SBC",
+        Opcode::SBCS => "SBCS",
         Opcode::ORR => "ORR",
         Opcode::EOR => "EOR",
         Opcode::MVN => "MVN",
+        Opcode::UBFX => "UBFX",
+        Opcode::SBFX => "SBFX",
+        Opcode::BFI => "BFI",
+        Opcode::BFXIL => "BFXIL",
+        Opcode::CLZ => "CLZ",
+        Opcode::CLS => "CLS",
+        Opcode::RBIT => "RBIT",
+        Opcode::REV => "REV",
+        Opcode::REV16 => "REV16",
+        Opcode::REV32 => "REV32",
+        Opcode::LSL => "LSL",
+        Opcode::LSR => "LSR",
+        Opcode::ASR => "ASR",
+        Opcode::ROR => "ROR",
+        Opcode::CSEL => "CSEL",
+        Opcode::CSINC => "CSINC",
+        Opcode::CSINV => "CSINV",
+        Opcode::CSNEG => "CSNEG",
+        Opcode::CSET => "CSET",
+        Opcode::CSETM => "CSETM",
+        Opcode::CINC => "CINC",
         Opcode::EXIT => "EXIT",
         Opcode::CMP => "CMP",
-        Opcode::BEQ => "BEQ",
-        Opcode::BNE => "BNE",
-        Opcode::BLE => "BLE",
-        Opcode::BLT => "BLT",
-        Opcode::BGE => "BGE",
-        Opcode::BGT => "BGT",
+        Opcode::CCMP => "CCMP",
+        Opcode::CCMN => "CCMN",
+        Opcode::BCOND => "B.cond",
         Opcode::DSB => "DSB",
         Opcode::TST => "TST",
         Opcode::TEQ => "TEQ",
     }
 }
 
+// If the instruction writes the flags in addition to its destination register.
+pub(crate) fn sets_flags(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::ADDS | Opcode::SUBS | Opcode::ANDS | Opcode::ADCS | Opcode::SBCS)
+}
+
+// If the instruction reads the carry flag.
+pub(crate) fn reads_carry(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::ADC | Opcode::ADCS | Opcode::SBC | Opcode::SBCS)
+}
+
+// If the load has acquire semantics; the younger loads can't be performed before it.
+pub(crate) fn is_acquire(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::LDAR | Opcode::LDAPR)
+}
+
+// The type of execution unit that executes the opcode.
+pub(crate) fn unit_type(opcode: Opcode) -> UnitType {
+    match opcode {
+        Opcode::MUL |
+        Opcode::MADD |
+        Opcode::MSUB |
+        Opcode::MNEG |
+        Opcode::SMULL |
+        Opcode::UMULL |
+        Opcode::SMULH |
+        Opcode::UMULH => UnitType::Mul,
+        Opcode::SDIV |
+        Opcode::UDIV => UnitType::Div,
+        Opcode::LDR |
+        Opcode::LDRB |
+        Opcode::LDRH |
+        Opcode::LDRSB |
+        Opcode::LDRSH |
+        Opcode::LDRSW |
+        Opcode::LDP |
+        Opcode::LDAR |
+        Opcode::LDAPR => UnitType::Load,
+        Opcode::STR |
+        Opcode::STRB |
+        Opcode::STRH |
+        Opcode::STP |
+        Opcode::STLR => UnitType::Store,
+        Opcode::B |
+        Opcode::BX |
+        Opcode::BR |
+        Opcode::BL |
+        Opcode::BLR |
+        Opcode::RET |
+        Opcode::CBZ |
+        Opcode::CBNZ |
+        Opcode::TBZ |
+        Opcode::TBNZ |
+        Opcode::BCOND => UnitType::Branch,
+        _ => UnitType::Alu,
+    }
+}
+
 pub(crate) fn get_opcode(mnemonic: &str) -> Option<Opcode> {
     let string = mnemonic.to_uppercase();
     let mnemonic_uppercased = string.as_str();
@@ -125,35 +292,150 @@ pub(crate) fn get_opcode(mnemonic: &str) -> Option<Opcode> {
         "SUB" => Some(Opcode::SUB),
         "RSB" => Some(Opcode::RSB),
         "MUL" => Some(Opcode::MUL),
+        "MADD" => Some(Opcode::MADD),
+        "MSUB" => Some(Opcode::MSUB),
+        "MNEG" => Some(Opcode::MNEG),
+        "SMULL" => Some(Opcode::SMULL),
+        "UMULL" => Some(Opcode::UMULL),
+        "SMULH" => Some(Opcode::SMULH),
+        "UMULH" => Some(Opcode::UMULH),
         "SDIV" => Some(Opcode::SDIV),
+        "UDIV" => Some(Opcode::UDIV),
         "NEG" => Some(Opcode::NEG),
         "ADR" => Some(Opcode::ADR),
+        "ADRP" => Some(Opcode::ADRP),
         "LDR" => Some(Opcode::LDR),
+        "LDRB" => Some(Opcode::LDRB),
+        "LDRH" => Some(Opcode::LDRH),
+        "LDRSB" => Some(Opcode::LDRSB),
+        "LDRSH" => Some(Opcode::LDRSH),
+        "LDRSW" => Some(Opcode::LDRSW),
+        "LDP" => Some(Opcode::LDP),
+        "LDAR" => Some(Opcode::LDAR),
+        "LDAPR" => Some(Opcode::LDAPR),
         "STR" => Some(Opcode::STR),
+        "STRB" => Some(Opcode::STRB),
+        "STRH" => Some(Opcode::STRH),
+        "STP" => Some(Opcode::STP),
+        "STLR" => Some(Opcode::STLR),
         "NOP" => Some(Opcode::NOP),
         "PRINTR" => Some(Opcode::PRINTR),
         "MOV" => Some(Opcode::MOV),
+        "MOVZ" => Some(Opcode::MOVZ),
+        "MOVN" => Some(Opcode::MOVN),
+        "MOVK" => Some(Opcode::MOVK),
         "B" => Some(Opcode::B),
         "RET" => Some(Opcode::RET),
         "BX" => Some(Opcode::BX),
+        "BR" => Some(Opcode::BR),
+        "BLR" => Some(Opcode::BLR),
         "CBZ" => Some(Opcode::CBZ),
         "CBNZ" => Some(Opcode::CBNZ),
+        "TBZ" => Some(Opcode::TBZ),
+        "TBNZ" => Some(Opcode::TBNZ),
         "AND" => Some(Opcode::AND),
+        "ANDS" => Some(Opcode::ANDS),
+        "ADDS" => Some(Opcode::ADDS),
+        "ADC" => Some(Opcode::ADC),
+        "ADCS" => Some(Opcode::ADCS),
+        "SUBS" => Some(Opcode::SUBS),
+        "SBC" => Some(Opcode::SBC),
+        "SBCS" => Some(Opcode::SBCS),
         "ORR" => Some(Opcode::ORR),
         "EOR" => Some(Opcode::EOR),
         "MVN" => Some(Opcode::MVN),
+        "UBFX" => Some(Opcode::UBFX),
+        "SBFX" => Some(Opcode::SBFX),
+        "BFI" => Some(Opcode::BFI),
+        "BFXIL" => Some(Opcode::BFXIL),
+        "CLZ" => Some(Opcode::CLZ),
+        "CLS" => Some(Opcode::CLS),
+        "RBIT" => Some(Opcode::RBIT),
+        "REV" => Some(Opcode::REV),
+        "REV16" => Some(Opcode::REV16),
+        "REV32" => Some(Opcode::REV32),
+        "LSL" => Some(Opcode::LSL),
+        "LSR" => Some(Opcode::LSR),
+        "ASR" => Some(Opcode::ASR),
+        "ROR" => Some(Opcode::ROR),
+        "CSEL" => Some(Opcode::CSEL),
+        "CSINC" => Some(Opcode::CSINC),
+        "CSINV" => Some(Opcode::CSINV),
+        "CSNEG" => Some(Opcode::CSNEG),
+        "CSET" => Some(Opcode::CSET),
+        "CSETM" => Some(Opcode::CSETM),
+        "CINC" => Some(Opcode::CINC),
         "BL" => Some(Opcode::BL),
         "EXIT" => Some(Opcode::EXIT),
         "CMP" => Some(Opcode::CMP),
-        "BEQ" => Some(Opcode::BEQ),
-        "BNE" => Some(Opcode::BNE),
-        "BLE" => Some(Opcode::BLE),
-        "BLT" => Some(Opcode::BLT),
-        "BGE" => Some(Opcode::BGE),
-        "BGT" => Some(Opcode::BGT),
+        "CCMP" => Some(Opcode::CCMP),
+        "CCMN" => Some(Opcode::CCMN),
         "DSB" => Some(Opcode::DSB),
         "TST" => Some(Opcode::TST),
         "TEQ" => Some(Opcode::TEQ),
+        _ => get_branch_condition(mnemonic_uppercased).map(|_| Opcode::BCOND),
+    }
+}
+
+// Returns the condition of a conditional branch mnemonic like 'B.HI'. The old
+// BEQ, BNE, BLE, BLT, BGE and BGT mnemonics are accepted as aliases.
+pub(crate) fn get_branch_condition(mnemonic: &str) -> Option<ConditionCode> {
+    let string = mnemonic.to_uppercase();
+    let mnemonic_uppercased = string.as_str();
+
+    match mnemonic_uppercased {
+        "BEQ" => Some(ConditionCode::EQ),
+        "BNE" => Some(ConditionCode::NE),
+        "BLE" => Some(ConditionCode::LE),
+        "BLT" => Some(ConditionCode::LT),
+        "BGE" => Some(ConditionCode::GE),
+        "BGT" => Some(ConditionCode::GT),
+        _ => get_condition_code(mnemonic_uppercased.strip_prefix("B.")?),
+    }
+}
+
+pub(crate) fn get_condition_code(name: &str) -> Option<ConditionCode> {
+    let string = name.to_uppercase();
+    let name_uppercased = string.as_str();
+
+    match name_uppercased {
+        "EQ" => Some(ConditionCode::EQ),
+        "NE" => Some(ConditionCode::NE),
+        "CS" | "HS" => Some(ConditionCode::CS),
+        "CC" | "LO" => Some(ConditionCode::CC),
+        "MI" => Some(ConditionCode::MI),
+        "PL" => Some(ConditionCode::PL),
+        "VS" => Some(ConditionCode::VS),
+        "VC" => Some(ConditionCode::VC),
+        "HI" => Some(ConditionCode::HI),
+        "LS" => Some(ConditionCode::LS),
+        "GE" => Some(ConditionCode::GE),
+        "LT" => Some(ConditionCode::LT),
+        "GT" => Some(ConditionCode::GT),
+        "LE" => Some(ConditionCode::LE),
+        "AL" => Some(ConditionCode::AL),
+        _ => None,
+    }
+}
+
+pub(crate) fn get_shift_type(name: &str) -> Option<ShiftType> {
+    match name.to_uppercase().as_str() {
+        "LSL" => Some(ShiftType::LSL),
+        "LSR" => Some(ShiftType::LSR),
+        "ASR" => Some(ShiftType::ASR),
+        "ROR" => Some(ShiftType::ROR),
+        _ => None,
+    }
+}
+
+pub(crate) fn get_extend_type(name: &str) -> Option<ExtendType> {
+    match name.to_uppercase().as_str() {
+        "UXTB" => Some(ExtendType::UXTB),
+        "UXTH" => Some(ExtendType::UXTH),
+        "UXTW" => Some(ExtendType::UXTW),
+        "SXTB" => Some(ExtendType::SXTB),
+        "SXTH" => Some(ExtendType::SXTH),
+        "SXTW" => Some(ExtendType::SXTW),
         _ => None,
     }
 }
@@ -172,14 +454,95 @@ pub(crate) const EXIT: Instr = Instr::Synchronization(
     }
 );
 
+// The shift that is applied to a shifted register operand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShiftType {
+    LSL,
+    LSR,
+    ASR,
+    ROR,
+}
+
+impl ShiftType {
+    // Shifts the value by the amount modulo 64.
+    pub fn apply(&self, value: DWordType, amount: u8) -> DWordType {
+        let amount = (amount & 63) as u32;
+        match self {
+            ShiftType::LSL => value << amount,
+            ShiftType::LSR => value >> amount,
+            ShiftType::ASR => ((value as i64) >> amount) as DWordType,
+            ShiftType::ROR => value.rotate_right(amount),
+        }
+    }
+
+    // Shifts the lower 32 bits of the value by the amount modulo 32. The result is zero extended.
+    pub fn apply_w(&self, value: DWordType, amount: u8) -> DWordType {
+        let value = value as u32;
+        let amount = (amount & 31) as u32;
+        let result = match self {
+            ShiftType::LSL => value << amount,
+            ShiftType::LSR => value >> amount,
+            ShiftType::ASR => ((value as i32) >> amount) as u32,
+            ShiftType::ROR => value.rotate_right(amount),
+        };
+        result as DWordType
+    }
+}
+
+// The extension that is applied to an extended register operand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtendType {
+    UXTB,
+    UXTH,
+    UXTW,
+    SXTB,
+    SXTH,
+    SXTW,
+}
+
+impl ExtendType {
+    // Zero or sign extends the lowest bits of the value and shifts the result left by the amount.
+    pub fn apply(&self, value: DWordType, amount: u8) -> DWordType {
+        let extended = match self {
+            ExtendType::UXTB => value as u8 as DWordType,
+            ExtendType::UXTH => value as u16 as DWordType,
+            ExtendType::UXTW => value as u32 as DWordType,
+            ExtendType::SXTB => value as i8 as i64 as DWordType,
+            ExtendType::SXTH => value as i16 as i64 as DWordType,
+            ExtendType::SXTW => value as i32 as i64 as DWordType,
+        };
+        extended << amount
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Operand2 {
     Immediate {
         value: DWordType,
     },
+    // the 16 bits immediate of the move wide instructions
+    ShiftedImmediate {
+        value: DWordType,
+        shift: u8,
+    },
+    // the bitfield of the bitfield move instructions
+    Bitfield {
+        lsb: u8,
+        width: u8,
+    },
     Register {
         reg_id: RegisterType,
     },
+    ShiftedRegister {
+        reg_id: RegisterType,
+        shift_type: ShiftType,
+        amount: u8,
+    },
+    ExtendedRegister {
+        reg_id: RegisterType,
+        extend_type: ExtendType,
+        amount: u8,
+    },
     Unused(),
 }
 
@@ -187,7 +550,15 @@ impl Display for Operand2 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Operand2::Immediate { value } => write!(f, "{}", *value),
+            Operand2::ShiftedImmediate { value, shift } => write!(f, "{}, LSL #{}", *value, shift),
+            Operand2::Bitfield { lsb, width } => write!(f, "#{}, #{}", lsb, width),
             Operand2::Register { reg_id } => write!(f, "{}", RegisterTypeDisplay { register: *reg_id }),
+            Operand2::ShiftedRegister { reg_id, shift_type, amount } =>
+                write!(f, "{}, {:?} #{}", RegisterTypeDisplay { register: *reg_id }, shift_type, amount),
+            Operand2::ExtendedRegister { reg_id, extend_type, amount: 0 } =>
+                write!(f, "{}, {:?}", RegisterTypeDisplay { register: *reg_id }, extend_type),
+            Operand2::ExtendedRegister { reg_id, extend_type, amount } =>
+                write!(f, "{}, {:?} #{}", RegisterTypeDisplay { register: *reg_id }, extend_type, amount),
             Operand2::Unused() => write!(f, "Unused"),
         }
     }
@@ -221,38 +592,116 @@ pub struct DataProcessing {
     pub rn: Option<RegisterType>,
     // Destination register
     pub rd: RegisterType,
-    // Second operand, which can be an immediate value or a shifted register.
+    // Second operand, which can be an immediate value, a register, a shifted register or an extended register.
     pub operand2: Operand2,
     // If the destination register should be read before it is written to
     pub rd_read: bool,
+    // The flags of a conditional compare when the condition doesn't hold.
+    pub nzcv: u8,
+    // The accumulator register of the multiply-accumulate instructions.
+    pub ra: Option<RegisterType>,
+    // The 32 bits variant that operates on the W views of the registers.
+    pub w_view: bool,
 }
 
 impl Display for DataProcessing {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self.opcode {
             Opcode::SUB |
+            Opcode::SUBS |
+            Opcode::SBC |
+            Opcode::SBCS |
+            Opcode::ADDS |
+            Opcode::ADC |
+            Opcode::ADCS |
+            Opcode::ANDS |
             Opcode::MUL |
+            Opcode::MNEG |
+            Opcode::SMULL |
+            Opcode::UMULL |
+            Opcode::SMULH |
+            Opcode::UMULH |
             Opcode::SDIV |
+            Opcode::UDIV |
             Opcode::AND |
             Opcode::ORR |
             Opcode::EOR |
+            Opcode::LSL |
+            Opcode::LSR |
+            Opcode::ASR |
+            Opcode::ROR |
             Opcode::RSB |
             Opcode::ADD => write!(f, "{:?} {}, {}, {}",
                                   self.opcode,
                                   RegisterTypeDisplay { register: self.rd },
                                   RegisterTypeDisplay { register: self.rn.unwrap() },
                                   self.operand2),
+            Opcode::MADD |
+            Opcode::MSUB => write!(f, "{:?} {}, {}, {}, {}",
+                                   self.opcode,
+                                   RegisterTypeDisplay { register: self.rd },
+                                   RegisterTypeDisplay { register: self.rn.unwrap() },
+                                   self.operand2,
+                                   RegisterTypeDisplay { register: self.ra.unwrap() }),
+            // the operand2 is the offset relative to the PC
+            Opcode::ADR |
+            Opcode::ADRP => if let Operand2::Immediate { value } = self.operand2 {
+                write!(f, "{:?} {}, PC{:+}", self.opcode, RegisterTypeDisplay { register: self.rd }, value as i64)
+            } else {
+                unreachable!()
+            },
             Opcode::NEG|
+            Opcode::MOVZ |
+            Opcode::MOVN |
+            Opcode::MOVK |
             Opcode::MOV => write!(f, "{:?} {}, {}",
                                   self.opcode, RegisterTypeDisplay { register: self.rd }, self.operand2),
+            Opcode::CLZ |
+            Opcode::CLS |
+            Opcode::RBIT |
+            Opcode::REV |
+            Opcode::REV16 |
+            Opcode::REV32 |
             Opcode::MVN => write!(f, "{:?} {}, {}",
                                   self.opcode, RegisterTypeDisplay { register: self.rd }, RegisterTypeDisplay{register:self.rn.unwrap()}),
+            Opcode::UBFX |
+            Opcode::SBFX |
+            Opcode::BFI |
+            Opcode::BFXIL => write!(f, "{:?} {}, {}, {}",
+                                    self.opcode,
+                                    RegisterTypeDisplay { register: self.rd },
+                                    RegisterTypeDisplay { register: self.rn.unwrap() },
+                                    self.operand2),
+            Opcode::CSEL |
+            Opcode::CSINC |
+            Opcode::CSINV |
+            Opcode::CSNEG => write!(f, "{:?} {}, {}, {}, {:?}",
+                                    self.opcode,
+                                    RegisterTypeDisplay { register: self.rd },
+                                    RegisterTypeDisplay { register: self.rn.unwrap() },
+                                    self.operand2,
+                                    self.condition),
+            Opcode::CSET |
+            Opcode::CSETM => write!(f, "{:?} {}, {:?}",
+                                    self.opcode, RegisterTypeDisplay { register: self.rd }, self.condition),
+            Opcode::CINC => write!(f, "{:?} {}, {}, {:?}",
+                                   self.opcode,
+                                   RegisterTypeDisplay { register: self.rd },
+                                   RegisterTypeDisplay { register: self.rn.unwrap() },
+                                   self.condition),
             Opcode::TEQ |
             Opcode::TST |
             Opcode::CMP => write!(f, "{:?} {}, {}",
                                   self.opcode,
                                   RegisterTypeDisplay { register: self.rn.unwrap() },
                                   self.operand2),
+            Opcode::CCMP |
+            Opcode::CCMN => write!(f, "{:?} {}, {}, #{}, {:?}",
+                                   self.opcode,
+                                   RegisterTypeDisplay { register: self.rn.unwrap() },
+                                   self.operand2,
+                                   self.nzcv,
+                                   self.condition),
             _ => unreachable!("Unknown opcode {:?}", self.opcode),
         }
     }
@@ -286,6 +735,28 @@ pub struct Branch {
     pub target: BranchTarget,
     // the register to test against.
     pub rt: Option<RegisterType>,
+    // the bit of the rt register that is tested by TBZ and TBNZ.
+    pub bit: u8,
+}
+
+impl Branch {
+    // True if the branch is conditional; so the direction needs to be predicted.
+    pub fn is_conditional(&self) -> bool {
+        match self.opcode {
+            Opcode::CBZ | Opcode::CBNZ | Opcode::TBZ | Opcode::TBNZ => true,
+            Opcode::BCOND => self.condition != ConditionCode::AL,
+            _ => false,
+        }
+    }
+
+    // True if the branch is a function return; so a RET or a BX to the link register.
+    pub fn is_return(&self) -> bool {
+        match self.opcode {
+            Opcode::RET => true,
+            Opcode::BX => matches!(self.target, BranchTarget::Register { register: LR }),
+            _ => false,
+        }
+    }
 }
 
 impl Display for Branch {
@@ -294,20 +765,61 @@ impl Display for Branch {
             Opcode::RET |
             Opcode::B |
             Opcode::BX |
-            Opcode::BL => write!(f, "{:?} {}", self.opcode, self.target),
+            Opcode::BR |
+            Opcode::BL |
+            Opcode::BLR => write!(f, "{:?} {}", self.opcode, self.target),
             Opcode::CBZ |
             Opcode::CBNZ => write!(f, "{:?} {}, {}", self.opcode, self.rt.unwrap(), self.target),
-            Opcode::BEQ |
-            Opcode::BNE |
-            Opcode::BLT |
-            Opcode::BLE |
-            Opcode::BGT |
-            Opcode::BGE => write!(f, "{:?} {}", self.opcode, self.target),
+            Opcode::TBZ |
+            Opcode::TBNZ => write!(f, "{:?} {}, #{}, {}", self.opcode, self.rt.unwrap(), self.bit, self.target),
+            Opcode::BCOND => write!(f, "B.{:?} {}", self.condition, self.target),
             _ => unreachable!("Unknown opcode {:?}", self.opcode),
         }
     }
 }
 
+// The size of the data a load or store transfers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataType {
+    Byte,
+    HalfWord,
+    Word,
+    DWord,
+}
+
+impl DataType {
+    // The size in bytes.
+    pub fn size(&self) -> u8 {
+        match self {
+            DataType::Byte => 1,
+            DataType::HalfWord => 2,
+            DataType::Word => 4,
+            DataType::DWord => 8,
+        }
+    }
+}
+
+// How the address of a load or store is calculated from the base register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressingMode {
+    // [Xn, #offset]; [Xn] has an offset of 0.
+    Offset { offset: i64 },
+    // [Xn, #offset]!; the address is written back to the base register.
+    PreIndex { offset: i64 },
+    // [Xn], #offset; the memory is accessed at the base register and the base register
+    // plus the offset is written back to the base register.
+    PostIndex { offset: i64 },
+    // [Xn, Xm, LSL #shift]
+    RegisterOffset { rm: RegisterType, shift: u8 },
+}
+
+impl AddressingMode {
+    // If the base register is updated by the load or store.
+    pub fn is_writeback(&self) -> bool {
+        matches!(self, AddressingMode::PreIndex { .. } | AddressingMode::PostIndex { .. })
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct LoadStore {
     pub opcode: Opcode,
@@ -315,16 +827,57 @@ pub struct LoadStore {
     pub loc: SourceLocation,
     pub rn: RegisterType,
     pub rd: RegisterType,
-    pub offset: u16,
+    // the second register of a load or store pair
+    pub rd2: Option<RegisterType>,
+    pub addressing_mode: AddressingMode,
+    // the size of the data that is loaded or stored
+    pub data_type: DataType,
+    // if the loaded value should be sign extended instead of zero extended
+    pub signed: bool,
+    // if the data registers are W registers; a signed load then sign extends to 32 bits
+    pub w_view: bool,
 }
 
 impl Display for LoadStore {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.opcode {
-            Opcode::LDR => write!(f, "LDR {}, [{}]", RegisterTypeDisplay { register: self.rd }, RegisterTypeDisplay { register: self.rn }),
-            Opcode::STR => write!(f, "STR {}, [{}]", RegisterTypeDisplay { register: self.rd }, RegisterTypeDisplay { register: self.rn }),
+            Opcode::LDP |
+            Opcode::STP if self.w_view =>
+                write!(f, "{:?} W{}, W{}, ", self.opcode, self.rd, self.rd2.unwrap())?,
+            Opcode::LDP |
+            Opcode::STP => write!(f, "{:?} {}, {}, ", self.opcode,
+                                  RegisterTypeDisplay { register: self.rd },
+                                  RegisterTypeDisplay { register: self.rd2.unwrap() })?,
+            Opcode::LDR |
+            Opcode::LDAR |
+            Opcode::LDAPR |
+            Opcode::STLR |
+            Opcode::LDRB |
+            Opcode::LDRH |
+            Opcode::LDRSB |
+            Opcode::LDRSH |
+            Opcode::LDRSW |
+            Opcode::STR |
+            Opcode::STRB |
+            Opcode::STRH => if self.w_view {
+                write!(f, "{:?} W{}, ", self.opcode, self.rd)?
+            } else {
+                write!(f, "{:?} {}, ", self.opcode, RegisterTypeDisplay { register: self.rd })?
+            },
             _ => unreachable!("Unknown opcode {:?}", self.opcode),
         }
+
+        let rn = RegisterTypeDisplay { register: self.rn };
+        match self.addressing_mode {
+            AddressingMode::Offset { offset: 0 } => write!(f, "[{}]", rn),
+            AddressingMode::Offset { offset } => write!(f, "[{}, #{}]", rn, offset),
+            AddressingMode::PreIndex { offset } => write!(f, "[{}, #{}]!", rn, offset),
+            AddressingMode::PostIndex { offset } => write!(f, "[{}], #{}", rn, offset),
+            AddressingMode::RegisterOffset { rm, shift: 0 } =>
+                write!(f, "[{}, {}]", rn, RegisterTypeDisplay { register: rm }),
+            AddressingMode::RegisterOffset { rm, shift } =>
+                write!(f, "[{}, {}, LSL #{}]", rn, RegisterTypeDisplay { register: rm }, shift),
+        }
     }
 }
 
@@ -361,6 +914,18 @@ pub enum Instr {
     Printr(Printr),
 }
 
+impl Instr {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Instr::DataProcessing(dp) => dp.opcode,
+            Instr::Branch(branch) => branch.opcode,
+            Instr::LoadStore(load_store) => load_store.opcode,
+            Instr::Synchronization(synchronization) => synchronization.opcode,
+            Instr::Printr(_) => Opcode::PRINTR,
+        }
+    }
+}
+
 impl Display for Instr {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
@@ -378,6 +943,8 @@ pub(crate) struct InstrQueueSlot {
     // The pc of the current instr.
     pub(crate) pc: usize,
     pub(crate) branch_target_predicted: usize,
+    // set when the pc is outside of the program; the instr is then a NOP that faults when it retires.
+    pub(crate) fetch_fault: bool,
 }
 
 // The InstrQueue sits between frontend and backend
@@ -393,7 +960,7 @@ impl InstrQueue {
         let mut slots = Vec::with_capacity(capacity as usize);
 
         for _ in 0..capacity {
-            slots.push(InstrQueueSlot { pc: 0, branch_target_predicted: 0, instr: Rc::new(NOP) });
+            slots.push(InstrQueueSlot { pc: 0, branch_target_predicted: 0, instr: Rc::new(NOP), fetch_fault: false });
         }
 
         InstrQueue {
@@ -451,7 +1018,9 @@ pub(crate) const INSTR_FLAG_ROB_SYNC: u8 = 2;
 
 pub struct Data {
     pub value: DWordType,
+    // the byte address of the data
     pub offset: u64,
+    pub data_type: DataType,
 }
 
 pub struct Program {
@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-use crate::instructions::instructions::{DWordType, RegisterType};
+use crate::instructions::instructions::{ConditionCode, DataType, DWordType, RegisterType};
 
 /// The AST for an AssemblyFile
 ///
@@ -16,6 +16,8 @@ use crate::instructions::instructions::{DWordType, RegisterType};
 pub struct ASTRegisterOperand {
     pub register: RegisterType,
     pub pos: usize,
+    // true if the register is referred to by its 32 bits name (W0..W30)
+    pub w_view: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +26,13 @@ pub struct ASTImmediateOperand {
     pub pos: usize,
 }
 
+// The constant of the 'LDR Xd, =constant' pseudo instruction.
+#[derive(Debug, Clone)]
+pub struct ASTLiteralOperand {
+    pub value: u64,
+    pub pos: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct ASTLabelOperand {
     pub label: String,
@@ -36,6 +45,8 @@ pub struct ASTAddressOfOperand {
     pub label: String,
     pub pos: usize,
     pub offset: DWordType,
+    // true for ':lo12:label'; so only the lowest 12 bits of the address
+    pub lo12: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -44,16 +55,54 @@ pub struct ASTMemRegisterIndirectOperand {
     pub pos: usize,
 }
 
+#[derive(Debug, Clone)]
+pub struct ASTMemRegIndirectWithOffsetOperand {
+    pub register: RegisterType,
+    pub offset: i64,
+    // true for [Xn, #offset]! where the address is written back to the register
+    pub pre_index: bool,
+    pub pos: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ASTMemRegIndirectWithRegOffsetOperand {
+    pub register: RegisterType,
+    pub offset_register: RegisterType,
+    // the LSL amount of the offset register
+    pub shift: u8,
+    pub pos: usize,
+}
+
+// The shift or extension of the preceding register operand, e.g. 'LSL #3' or 'UXTW #2'. The name
+// is parsed as an identifier and the loader determines if it is a shift or an extension.
+#[derive(Debug, Clone)]
+pub struct ASTShiftOperand {
+    pub name: String,
+    pub amount: u64,
+    pub pos: usize,
+}
+
+// A condition code like 'EQ' that is used by e.g. the conditional select instructions. It is
+// parsed as a label, so that a label with the same name can still be used, and the loader
+// turns it into a condition.
+#[derive(Debug, Clone)]
+pub struct ASTConditionOperand {
+    pub condition: ConditionCode,
+    pub pos: usize,
+}
+
 #[derive(Debug, Clone)]
 pub enum ASTOperand {
     Register(ASTRegisterOperand),
     Immediate(ASTImmediateOperand),
+    Literal(ASTLiteralOperand),
     Label(ASTLabelOperand),
     AddressOf(ASTAddressOfOperand),
     MemRegisterIndirect(ASTMemRegisterIndirectOperand),
-    // Uncomment and add these if needed
-    // MemRegIndirectWithOffset(MemRegIndirectWithOffset),
-    // MemRegIndirectWithRegOffset(MemRegIndirectWithRegOffset),
+    MemRegIndirectWithOffset(ASTMemRegIndirectWithOffsetOperand),
+    MemRegIndirectWithRegOffset(ASTMemRegIndirectWithRegOffsetOperand),
+    Shift(ASTShiftOperand),
+    Condition(ASTConditionOperand),
     Unused(),
 }
 
@@ -62,9 +111,14 @@ impl ASTOperand {
         match self {
             ASTOperand::Register(_) => ASTOperandType::Register,
             ASTOperand::Immediate(_) => ASTOperandType::Immediate,
+            ASTOperand::Literal(_) => ASTOperandType::Literal,
             ASTOperand::Label(_) => ASTOperandType::Label,
             ASTOperand::AddressOf(_) => ASTOperandType::AddressOf,
             ASTOperand::MemRegisterIndirect(_) => ASTOperandType::MemRegisterIndirect,
+            ASTOperand::MemRegIndirectWithOffset(_) => ASTOperandType::MemRegIndirectWithOffset,
+            ASTOperand::MemRegIndirectWithRegOffset(_) => ASTOperandType::MemRegIndirectWithRegOffset,
+            ASTOperand::Shift(_) => ASTOperandType::Shift,
+            ASTOperand::Condition(_) => ASTOperandType::Condition,
             ASTOperand::Unused() => ASTOperandType::Unused,
         }
     }
@@ -73,9 +127,14 @@ impl ASTOperand {
 pub enum ASTOperandType{
     Register,
     Immediate,
+    Literal,
     Label,
     AddressOf,
     MemRegisterIndirect,
+    MemRegIndirectWithOffset,
+    MemRegIndirectWithRegOffset,
+    Shift,
+    Condition,
     Unused,
 }
 
@@ -84,9 +143,14 @@ impl ASTOperandType {
         match  self {
             ASTOperandType::Register => "Register",
             ASTOperandType::Immediate => "Immediate",
+            ASTOperandType::Literal => "Literal",
             ASTOperandType::Label => "Label",
             ASTOperandType::AddressOf => "AddressOf",
             ASTOperandType::MemRegisterIndirect => "MemRegisterIndirect",
+            ASTOperandType::MemRegIndirectWithOffset => "MemRegIndirectWithOffset",
+            ASTOperandType::MemRegIndirectWithRegOffset => "MemRegIndirectWithRegOffset",
+            ASTOperandType::Shift => "Shift",
+            ASTOperandType::Condition => "Condition",
             ASTOperandType::Unused => "Unused",
         }
     }
@@ -132,6 +196,7 @@ impl ASTOperand {
 #[derive(Debug)]
 pub struct ASTData {
     pub name: String,
+    pub data_type: DataType,
     pub value: u64,
     pub pos: usize,
 }
@@ -148,6 +213,7 @@ pub struct ASTInstr {
     pub op1: ASTOperand,
     pub op2: ASTOperand,
     pub op3: ASTOperand,
+    pub op4: ASTOperand,
     pub pos: usize,
 }
 
@@ -156,6 +222,7 @@ impl ASTInstr {
         if !self.op1.accept(visitor) { return false; }
         if !self.op2.accept(visitor) { return false; }
         if !self.op3.accept(visitor) { return false; }
+        if !self.op4.accept(visitor) { return false; }
         visitor.visit_instr(self)
     }
 }
@@ -7,11 +7,11 @@ use lalrpop_util::ParseError;
 use regex::Regex;
 
 use crate::assembly;
-use crate::cpu::{CPSR, CPUConfig, GENERAL_ARG_REG_CNT, LR};
-use crate::instructions::instructions::{Branch, BranchTarget, ConditionCode, Data, DataProcessing, DWordType,
-                                        get_opcode, Instr, LoadStore, Opcode, Operand2, Printr, Program, RegisterType,
-                                        SourceLocation, Synchronization};
-use crate::loader::ast::{ASTAssemblyFile, ASTData, ASTDirective, ASTInstr, ASTLabel, ASTOperand, ASTOperandType, ASTVisitor};
+use crate::cpu::{CPSR, CPUConfig, LR, PC, SP, UnitType, XZR};
+use crate::instructions::instructions::{AddressingMode, Branch, BranchTarget, ConditionCode, Data, DataProcessing, DataType, DWordType,
+                                        ExtendType, get_branch_condition, get_condition_code, get_extend_type, get_opcode, get_shift_type, Instr, LoadStore, Opcode, Operand2, Printr, Program, RegisterType,
+                                        ShiftType, SourceLocation, Synchronization, unit_type};
+use crate::loader::ast::{ASTAddressOfOperand, ASTAssemblyFile, ASTConditionOperand, ASTShiftOperand, ASTData, ASTDirective, ASTInstr, ASTLabel, ASTOperand, ASTOperandType, ASTVisitor};
 use crate::loader::loader::LoadError::AnalysisError;
 
 struct Loader {
@@ -113,16 +113,155 @@ impl Loader {
     }
 }
 
-pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: SourceLocation) -> Result<Instr, String> {
+// The condition is the condition of a conditional branch; for any other instruction it is AL.
+pub(crate) fn create_instr(opcode: Opcode, condition: ConditionCode, operands: &Vec<ASTOperand>, loc: SourceLocation) -> Result<Instr, String> {
+    let w_view = register_view(opcode, operands)?;
+    let reg_size: DWordType = if w_view { 32 } else { 64 };
+
     let instr = match opcode {
         Opcode::SUB |
+        Opcode::SUBS |
+        Opcode::ADDS |
+        Opcode::ANDS |
         Opcode::MUL |
         Opcode::SDIV |
+        Opcode::UDIV |
         Opcode::AND |
         Opcode::ORR |
         Opcode::EOR |
         Opcode::RSB |
         Opcode::ADD => {
+            // the last operand can be a shifted or extended register
+            if operands.len() != 4 {
+                validate_operand_count(3, operands, opcode, loc)?;
+            }
+
+            let rd = match &operands[0] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let rn = match &operands[1] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let operand2 = create_operand2(opcode, operands, 2, reg_size)?;
+
+            Instr::DataProcessing(
+                DataProcessing {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rn: Some(rn),
+                    rd,
+                    rd_read: false,
+                    operand2,
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
+                }
+            )
+        }
+        Opcode::MADD |
+        Opcode::MSUB |
+        Opcode::MNEG |
+        Opcode::SMULL |
+        Opcode::UMULL |
+        Opcode::SMULH |
+        Opcode::UMULH => {
+            // MADD and MSUB have an accumulator register as fourth operand
+            let accumulate = matches!(opcode, Opcode::MADD | Opcode::MSUB);
+            validate_operand_count(if accumulate { 4 } else { 3 }, operands, opcode, loc)?;
+
+            let rd = match &operands[0] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let rn = match &operands[1] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let operand2 = match &operands[2] {
+                ASTOperand::Register(o) => Operand2::Register { reg_id: o.register },
+                _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let ra = if accumulate {
+                match &operands[3] {
+                    ASTOperand::Register(o) => Some(o.register),
+                    _ => return Err(type_mismatch(opcode, 3, &operands[3],
+                                                  vec![ASTOperandType::Register]))
+                }
+            } else {
+                None
+            };
+
+            Instr::DataProcessing(
+                DataProcessing {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rn: Some(rn),
+                    rd,
+                    rd_read: false,
+                    operand2,
+                    nzcv: 0,
+                    ra,
+                    w_view,
+                }
+            )
+        }
+        Opcode::ADC |
+        Opcode::ADCS |
+        Opcode::SBC |
+        Opcode::SBCS => {
+            validate_operand_count(3, operands, opcode, loc)?;
+
+            let rd = match &operands[0] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let rn = match &operands[1] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let operand2 = match &operands[2] {
+                ASTOperand::Register(o) => Operand2::Register { reg_id: o.register },
+                _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            Instr::DataProcessing(
+                DataProcessing {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rn: Some(rn),
+                    rd,
+                    rd_read: false,
+                    operand2,
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
+                }
+            )
+        }
+        Opcode::LSL |
+        Opcode::LSR |
+        Opcode::ASR |
+        Opcode::ROR => {
             validate_operand_count(3, operands, opcode, loc)?;
 
             let rd = match &operands[0] {
@@ -137,8 +276,11 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                                               vec![ASTOperandType::Register]))
             };
 
+            // the shift amount in a register is taken modulo the register size when executed
             let operand2 = match &operands[2] {
                 ASTOperand::Register(register) => Operand2::Register { reg_id: register.register },
+                ASTOperand::Immediate(immediate) if immediate.value > reg_size - 1 =>
+                    return Err(format!("{:?} shift amount {} is out of range [0, {}]", opcode, immediate.value, reg_size - 1)),
                 ASTOperand::Immediate(immediate) => Operand2::Immediate { value: immediate.value },
                 _ => return Err(type_mismatch(opcode, 2, &operands[2],
                                               vec![ASTOperandType::Register, ASTOperandType::Immediate]))
@@ -153,9 +295,135 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     rd,
                     rd_read: false,
                     operand2,
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
+                }
+            )
+        }
+        Opcode::CSEL |
+        Opcode::CSINC |
+        Opcode::CSINV |
+        Opcode::CSNEG |
+        Opcode::CSET |
+        Opcode::CSETM |
+        Opcode::CINC => {
+            // CSET and CSETM have no source registers and CINC has a single source register
+            let source_cnt = match opcode {
+                Opcode::CSET | Opcode::CSETM => 0,
+                Opcode::CINC => 1,
+                _ => 2,
+            };
+            validate_operand_count(source_cnt + 2, operands, opcode, loc)?;
+
+            let rd = match &operands[0] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let rn = if source_cnt > 0 {
+                match &operands[1] {
+                    ASTOperand::Register(o) => Some(o.register),
+                    _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                                  vec![ASTOperandType::Register]))
+                }
+            } else {
+                None
+            };
+
+            let operand2 = if source_cnt > 1 {
+                match &operands[2] {
+                    ASTOperand::Register(o) => Operand2::Register { reg_id: o.register },
+                    _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                                                  vec![ASTOperandType::Register]))
+                }
+            } else {
+                Operand2::Unused()
+            };
+
+            let condition = match condition_operand(&operands[source_cnt + 1]) {
+                Some(condition) => condition,
+                None => return Err(type_mismatch(opcode, (source_cnt + 1) as i32, &operands[source_cnt + 1],
+                                                 vec![ASTOperandType::Condition]))
+            };
+
+            // the aliases are defined in terms of the inverted condition, which doesn't exist for AL
+            if condition == ConditionCode::AL && source_cnt < 2 {
+                return Err(format!("{:?} doesn't support the AL condition", opcode));
+            }
+
+            Instr::DataProcessing(
+                DataProcessing {
+                    opcode,
+                    condition,
+                    loc,
+                    rn,
+                    rd,
+                    rd_read: false,
+                    operand2,
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
                 }
             )
         }
+        Opcode::UBFX |
+        Opcode::SBFX |
+        Opcode::BFI |
+        Opcode::BFXIL => {
+            validate_operand_count(4, operands, opcode, loc)?;
+
+            let rd = match &operands[0] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let rn = match &operands[1] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let lsb = match &operands[2] {
+                ASTOperand::Immediate(o) if o.value > reg_size - 1 =>
+                    return Err(format!("{:?} lsb {} is out of range [0, {}]", opcode, o.value, reg_size - 1)),
+                ASTOperand::Immediate(o) => o.value,
+                _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                                              vec![ASTOperandType::Immediate]))
+            };
+
+            let width = match &operands[3] {
+                ASTOperand::Immediate(o) if o.value < 1 || o.value > reg_size - lsb =>
+                    return Err(format!("{:?} width {} is out of range [1, {}]", opcode, o.value, reg_size - lsb)),
+                ASTOperand::Immediate(o) => o.value,
+                _ => return Err(type_mismatch(opcode, 3, &operands[3],
+                                              vec![ASTOperandType::Immediate]))
+            };
+
+            // BFI and BFXIL keep the bits of the destination outside of the bitfield
+            Instr::DataProcessing(
+                DataProcessing {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rn: Some(rn),
+                    rd,
+                    rd_read: matches!(opcode, Opcode::BFI | Opcode::BFXIL),
+                    operand2: Operand2::Bitfield { lsb: lsb as u8, width: width as u8 },
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
+                }
+            )
+        }
+        Opcode::CLZ |
+        Opcode::CLS |
+        Opcode::RBIT |
+        Opcode::REV |
+        Opcode::REV16 |
+        Opcode::REV32 |
         Opcode::MVN |
         Opcode::NEG => {
             validate_operand_count(2, operands, opcode, loc)?;
@@ -181,13 +449,19 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     rd,
                     rd_read: false,
                     operand2: Operand2::Unused(),
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
                 }
             )
         }
         Opcode::TEQ |
         Opcode::TST |
         Opcode::CMP => {
-            validate_operand_count(2, operands, opcode, loc)?;
+            // the last operand can be a shifted or extended register
+            if operands.len() != 3 {
+                validate_operand_count(2, operands, opcode, loc)?;
+            }
 
             let rd = CPSR as RegisterType;
 
@@ -197,22 +471,69 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                                               vec![ASTOperandType::Register]))
             };
 
+            let operand2 = create_operand2(opcode, operands, 1, reg_size)?;
+
+            Instr::DataProcessing(
+                DataProcessing {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rn: Some(rn),
+                    rd,
+                    rd_read: true,
+                    operand2,
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
+                }
+            )
+        }
+        Opcode::CCMP |
+        Opcode::CCMN => {
+            validate_operand_count(4, operands, opcode, loc)?;
+
+            let rn = match &operands[0] {
+                ASTOperand::Register(o) => o.register,
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
             let operand2 = match &operands[1] {
                 ASTOperand::Register(register) => Operand2::Register { reg_id: register.register },
+                ASTOperand::Immediate(immediate) if immediate.value > 31 =>
+                    return Err(format!("{:?} immediate {} is out of range [0, 31]", opcode, immediate.value)),
                 ASTOperand::Immediate(immediate) => Operand2::Immediate { value: immediate.value },
-                _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
                                               vec![ASTOperandType::Register, ASTOperandType::Immediate]))
             };
 
+            let nzcv = match &operands[2] {
+                ASTOperand::Immediate(immediate) if immediate.value > 15 =>
+                    return Err(format!("{:?} nzcv {} is out of range [0, 15]", opcode, immediate.value)),
+                ASTOperand::Immediate(immediate) => immediate.value as u8,
+                _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                                              vec![ASTOperandType::Immediate]))
+            };
+
+            let condition = match condition_operand(&operands[3]) {
+                Some(condition) => condition,
+                None => return Err(type_mismatch(opcode, 3, &operands[3],
+                                                 vec![ASTOperandType::Condition]))
+            };
+
+            // the cpsr is read because the bits other than the flags are preserved
             Instr::DataProcessing(
                 DataProcessing {
                     opcode,
-                    condition: ConditionCode::AL,
+                    condition,
                     loc,
                     rn: Some(rn),
-                    rd,
+                    rd: CPSR as RegisterType,
                     rd_read: true,
                     operand2,
+                    nzcv,
+                    ra: None,
+                    w_view,
                 }
             )
         }
@@ -227,9 +548,11 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
 
             let operand2 = match &operands[1] {
                 ASTOperand::Register(register) => Operand2::Register { reg_id: register.register },
+                ASTOperand::Immediate(immediate) if !is_mov_immediate(immediate.value, w_view) =>
+                    return Err(format!("{:?} immediate {:#x} can't be encoded, use LDR Xd, ={:#x} instead", opcode, immediate.value, immediate.value)),
                 ASTOperand::Immediate(immediate) => Operand2::Immediate { value: immediate.value },
                 ASTOperand::AddressOf(address_of) => Operand2::Immediate { value: address_of.offset },
-                _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
                                               vec![ASTOperandType::Register, ASTOperandType::Immediate, ASTOperandType::AddressOf]))
             };
 
@@ -242,13 +565,19 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     rd,
                     rd_read: false,
                     operand2,
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
                 }
             )
         }
-        Opcode::ADR => { panic!() }
-        Opcode::STR |
-        Opcode::LDR => {
-            validate_operand_count(2, operands, opcode, loc)?;
+        Opcode::MOVZ |
+        Opcode::MOVN |
+        Opcode::MOVK => {
+            // the optional shift selects which 16 bits of the register are written
+            if operands.len() != 3 {
+                validate_operand_count(2, operands, opcode, loc)?;
+            }
 
             let rd = match &operands[0] {
                 ASTOperand::Register(o) => o.register,
@@ -256,8 +585,181 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                                               vec![ASTOperandType::Register]))
             };
 
+            let value = match &operands[1] {
+                ASTOperand::Immediate(immediate) if immediate.value > 0xFFFF =>
+                    return Err(format!("{:?} immediate {} is out of range [0, 65535]", opcode, immediate.value)),
+                ASTOperand::Immediate(immediate) => immediate.value,
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                              vec![ASTOperandType::Immediate]))
+            };
+
+            let shift = match operands.get(2) {
+                None => 0,
+                Some(operand) => match shift_operand(operand) {
+                    Some((ShiftType::LSL, amount)) if amount % 16 == 0 && amount as DWordType <= reg_size - 16 =>
+                        amount as u8,
+                    Some(_) if w_view =>
+                        return Err(format!("{:?} only supports LSL #0 or #16 for a W register", opcode)),
+                    Some(_) =>
+                        return Err(format!("{:?} only supports LSL #0, #16, #32 or #48", opcode)),
+                    None => return Err(type_mismatch(opcode, 2, operand,
+                                                     vec![ASTOperandType::Shift]))
+                }
+            };
+
+            // MOVK keeps the other bits of the register
+            Instr::DataProcessing(
+                DataProcessing {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rn: None,
+                    rd,
+                    rd_read: opcode == Opcode::MOVK,
+                    operand2: Operand2::ShiftedImmediate { value, shift },
+                    nzcv: 0,
+                    ra: None,
+                    w_view,
+                }
+            )
+        }
+        Opcode::ADR |
+        Opcode::ADRP => unreachable!("{:?} is created by create_pc_relative", opcode),
+        Opcode::STR |
+        Opcode::STRB |
+        Opcode::STRH |
+        Opcode::LDR |
+        Opcode::LDRB |
+        Opcode::LDRH |
+        Opcode::LDRSB |
+        Opcode::LDRSH |
+        Opcode::LDRSW => {
+            // the post-index addressing mode has the offset as a third operand
+            if operands.len() != 3 {
+                validate_operand_count(2, operands, opcode, loc)?;
+            }
+
+            let (rd, w_view) = match &operands[0] {
+                ASTOperand::Register(o) => (o.register, o.w_view),
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let (rn, addressing_mode) = create_addressing_mode(opcode, operands, 1)?;
+
+            let data_type = match opcode {
+                Opcode::STRB |
+                Opcode::LDRB |
+                Opcode::LDRSB => DataType::Byte,
+                Opcode::STRH |
+                Opcode::LDRH |
+                Opcode::LDRSH => DataType::HalfWord,
+                Opcode::LDRSW => DataType::Word,
+                // the size of LDR and STR depends on the register view
+                _ => if w_view { DataType::Word } else { DataType::DWord },
+            };
+
+            // the byte and halfword loads and stores need a W register; the signed byte and
+            // halfword loads can sign extend to a W or an X register.
+            match opcode {
+                Opcode::LDRB |
+                Opcode::LDRH |
+                Opcode::STRB |
+                Opcode::STRH if !w_view =>
+                    return Err(format!("{:?} only supports a W register", opcode)),
+                Opcode::LDRSW if w_view =>
+                    return Err(format!("{:?} only supports sign extension to a 64 bits register", opcode)),
+                _ => {}
+            }
+
+            let signed = matches!(opcode, Opcode::LDRSB | Opcode::LDRSH | Opcode::LDRSW);
+
+            validate_addressing_mode(&addressing_mode, data_type, opcode)?;
+
+            if addressing_mode.is_writeback() && rn == rd {
+                return Err(format!("{:?} can't use the same register for the data and for the base with writeback", opcode));
+            }
+
+            Instr::LoadStore(
+                LoadStore {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rd,
+                    rd2: None,
+                    rn,
+                    addressing_mode,
+                    data_type,
+                    signed,
+                    w_view,
+                }
+            )
+        }
+        Opcode::LDP |
+        Opcode::STP => {
+            // the post-index addressing mode has the offset as a fourth operand
+            if operands.len() != 4 {
+                validate_operand_count(3, operands, opcode, loc)?;
+            }
+
+            let (rd, w_view) = match &operands[0] {
+                ASTOperand::Register(o) => (o.register, o.w_view),
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let rd2 = match &operands[1] {
+                ASTOperand::Register(o) if o.w_view == w_view => o.register,
+                ASTOperand::Register(_) =>
+                    return Err(format!("{:?} requires both registers to be X or both to be W registers", opcode)),
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            let (rn, addressing_mode) = create_addressing_mode(opcode, operands, 2)?;
+
+            let data_type = if w_view { DataType::Word } else { DataType::DWord };
+
+            validate_pair_addressing_mode(&addressing_mode, data_type, opcode)?;
+
+            if opcode == Opcode::LDP && rd == rd2 {
+                return Err(format!("{:?} can't load both values into the same register", opcode));
+            }
+
+            if addressing_mode.is_writeback() && (rn == rd || rn == rd2) {
+                return Err(format!("{:?} can't use the same register for the data and for the base with writeback", opcode));
+            }
+
+            Instr::LoadStore(
+                LoadStore {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    rd,
+                    rd2: Some(rd2),
+                    rn,
+                    addressing_mode,
+                    data_type,
+                    signed: false,
+                    w_view,
+                }
+            )
+        }
+        Opcode::LDAR |
+        Opcode::LDAPR |
+        Opcode::STLR => {
+            validate_operand_count(2, operands, opcode, loc)?;
+
+            let (rd, w_view) = match &operands[0] {
+                ASTOperand::Register(o) => (o.register, o.w_view),
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            // only the base register addressing mode is supported
             let rn = match &operands[1] {
-                ASTOperand::MemRegisterIndirect(mem_register_indirect) => mem_register_indirect.register,
+                ASTOperand::MemRegisterIndirect(o) => o.register,
+                ASTOperand::MemRegIndirectWithOffset(o) if o.offset == 0 && !o.pre_index => o.register,
                 _ => return Err(type_mismatch(opcode, 1, &operands[1],
                                               vec![ASTOperandType::MemRegisterIndirect]))
             };
@@ -268,8 +770,12 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     condition: ConditionCode::AL,
                     loc,
                     rd,
+                    rd2: None,
                     rn,
-                    offset: 0,
+                    addressing_mode: AddressingMode::Offset { offset: 0 },
+                    data_type: if w_view { DataType::Word } else { DataType::DWord },
+                    signed: false,
+                    w_view,
                 }
             )
         }
@@ -314,6 +820,7 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     link_bit: false,
                     target: BranchTarget::Register { register: target },
                     rt: None,
+                    bit: 0,
                 }
             )
         }
@@ -334,10 +841,13 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     link_bit: false,
                     target: BranchTarget::Immediate { offset: offset as u32 },
                     rt: None,
+                    bit: 0,
                 }
             )
         }
-        Opcode::BX => {
+        Opcode::BX |
+        Opcode::BR |
+        Opcode::BLR => {
             validate_operand_count(1, operands, opcode, loc)?;
 
             let target = match &operands[0] {
@@ -351,9 +861,10 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     opcode,
                     condition: ConditionCode::AL,
                     loc,
-                    link_bit: false,
+                    link_bit: opcode == Opcode::BLR,
                     target: BranchTarget::Register { register: target },
                     rt: None,
+                    bit: 0,
                 }
             )
         }
@@ -373,6 +884,45 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     link_bit: true,
                     target: BranchTarget::Immediate { offset: target as u32 },
                     rt: None,
+                    bit: 0,
+                }
+            )
+        }
+        Opcode::TBZ |
+        Opcode::TBNZ => {
+            validate_operand_count(3, operands, opcode, loc)?;
+
+            let (rt, w_view) = match &operands[0] {
+                ASTOperand::Register(o) => (o.register, o.w_view),
+                _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                              vec![ASTOperandType::Register]))
+            };
+
+            // the bit is tested in the register view; so a W register only has 32 bits
+            let max_bit = if w_view { 31 } else { 63 };
+            let bit = match &operands[1] {
+                ASTOperand::Immediate(o) if o.value > max_bit =>
+                    return Err(format!("{:?} bit {} is out of range [0, {}]", opcode, o.value, max_bit)),
+                ASTOperand::Immediate(o) => o.value as u8,
+                _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                              vec![ASTOperandType::Immediate]))
+            };
+
+            let target = match &operands[2] {
+                ASTOperand::Label(o) =>  o.offset ,
+                _ => return Err(type_mismatch(opcode, 2, &operands[2],
+                                              vec![ASTOperandType::Label]))
+            };
+
+            Instr::Branch(
+                Branch {
+                    opcode,
+                    condition: ConditionCode::AL,
+                    loc,
+                    link_bit: false,
+                    target: BranchTarget::Immediate { offset: target as u32 },
+                    rt: Some(rt),
+                    bit,
                 }
             )
         }
@@ -400,15 +950,11 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
                     link_bit: false,
                     target: BranchTarget::Immediate { offset: target as u32 },
                     rt: Some(rt),
+                    bit: 0,
                 }
             )
         }
-        Opcode::BEQ |
-        Opcode::BNE |
-        Opcode::BLT |
-        Opcode::BLE |
-        Opcode::BGT |
-        Opcode::BGE => {
+        Opcode::BCOND => {
             validate_operand_count(1, operands, opcode, loc)?;
 
             let offset = match &operands[0] {
@@ -420,11 +966,12 @@ pub(crate) fn create_instr(opcode: Opcode, operands: &Vec<ASTOperand>, loc: Sour
             Instr::Branch(
                 Branch {
                     opcode,
-                    condition: ConditionCode::AL,
+                    condition,
                     loc,
                     link_bit: false,
                     target: BranchTarget::Immediate { offset: offset as u32 },
                     rt: Some(CPSR),
+                    bit: 0,
                 }
             )
         }
@@ -460,6 +1007,403 @@ fn type_mismatch(opcode: Opcode, op_index: i32, found: &ASTOperand, acceptable_t
             opcode, acceptable_names_str, op_index + 1, found.get_type().base_name())
 }
 
+// Returns the condition of a condition operand. A label or variable with the name of a condition
+// code, e.g. 'lt', is resolved as a label; in the position of a condition it is the condition.
+fn condition_operand(operand: &ASTOperand) -> Option<ConditionCode> {
+    match operand {
+        ASTOperand::Condition(o) => Some(o.condition),
+        ASTOperand::Label(o) => get_condition_code(&o.label),
+        ASTOperand::AddressOf(o) if !o.lo12 => get_condition_code(&o.label),
+        _ => None,
+    }
+}
+
+// Returns the shift type and amount of a shift operand like 'LSL #3'.
+fn shift_operand(operand: &ASTOperand) -> Option<(ShiftType, u64)> {
+    match operand {
+        ASTOperand::Shift(o) => get_shift_type(&o.name).map(|shift_type| (shift_type, o.amount)),
+        _ => None,
+    }
+}
+
+// Returns the extension and amount of an extend operand like 'UXTW #2'. An extension without
+// an amount, e.g. 'uxtw', is resolved as a label if a label with that name exists.
+fn extend_operand(operand: &ASTOperand) -> Option<(ExtendType, u64)> {
+    match operand {
+        ASTOperand::Shift(o) => get_extend_type(&o.name).map(|extend_type| (extend_type, o.amount)),
+        ASTOperand::Label(o) => get_extend_type(&o.label).map(|extend_type| (extend_type, 0)),
+        ASTOperand::AddressOf(o) if !o.lo12 => get_extend_type(&o.label).map(|extend_type| (extend_type, 0)),
+        _ => None,
+    }
+}
+
+// Returns true if the instruction operates on the W views of the registers. The views can't be
+// mixed, except for the register of an extension and the sources of the long multiplies.
+fn register_view(opcode: Opcode, operands: &Vec<ASTOperand>) -> Result<bool, String> {
+    // the size of a load or store is determined by the view of the data register
+    if matches!(unit_type(opcode), UnitType::Load | UnitType::Store) {
+        return Ok(false);
+    }
+
+    let mut w_view = None;
+    for (k, operand) in operands.iter().enumerate() {
+        let register = match operand {
+            ASTOperand::Register(register) => register,
+            _ => continue,
+        };
+
+        if operands.get(k + 1).and_then(extend_operand).is_some() || (k > 0 && matches!(opcode, Opcode::SMULL | Opcode::UMULL)) {
+            continue;
+        }
+
+        // the bit test branches can test a W register
+        let x_only = (unit_type(opcode) == UnitType::Branch && !matches!(opcode, Opcode::TBZ | Opcode::TBNZ))
+            || matches!(opcode, Opcode::PRINTR | Opcode::SMULL | Opcode::UMULL | Opcode::SMULH | Opcode::UMULH | Opcode::REV32);
+        if register.w_view && x_only {
+            return Err(format!("{:?} doesn't support the W{} register", opcode, register.register));
+        }
+
+        if *w_view.get_or_insert(register.w_view) != register.w_view {
+            return Err(format!("{:?} can't mix W and X registers", opcode));
+        }
+    }
+    Ok(w_view.unwrap_or(false))
+}
+
+// SP is only accepted by the instructions that can address it; in the other positions register
+// 31 is the zero register. The base of a memory operand can't be the zero register.
+fn validate_special_registers(opcode: Opcode, operands: &Vec<ASTOperand>) -> Result<(), String> {
+    let shifted = operands.last().and_then(shift_operand).is_some();
+    for (k, operand) in operands.iter().enumerate() {
+        let (base, offset_register) = match operand {
+            ASTOperand::Register(register) if register.register == SP => {
+                let accepts_sp = match opcode {
+                    Opcode::MOV | Opcode::PRINTR => true,
+                    Opcode::ADD | Opcode::SUB => k < 2 && !shifted,
+                    Opcode::ADDS | Opcode::SUBS => k == 1 && !shifted,
+                    Opcode::CMP => k == 0 && !shifted,
+                    _ => false,
+                };
+                if !accepts_sp {
+                    return Err(format!("{:?} doesn't support SP as argument nr {}", opcode, k + 1));
+                }
+                continue;
+            }
+            ASTOperand::MemRegisterIndirect(mem) => (mem.register, None),
+            ASTOperand::MemRegIndirectWithOffset(mem) => (mem.register, None),
+            ASTOperand::MemRegIndirectWithRegOffset(mem) => (mem.register, Some(mem.offset_register)),
+            _ => continue,
+        };
+
+        if base == XZR || base == PC {
+            return Err(format!("{:?} doesn't support {} as base register", opcode, if base == XZR { "XZR" } else { "PC" }));
+        }
+
+        if offset_register == Some(SP) {
+            return Err(format!("{:?} doesn't support SP as offset register", opcode));
+        }
+    }
+    Ok(())
+}
+
+// Creates the Operand2 from the operand at the given index. If there is an operand after it,
+// it is the shift or the extension of the register.
+fn create_operand2(opcode: Opcode, operands: &Vec<ASTOperand>, index: usize, reg_size: DWordType) -> Result<Operand2, String> {
+    let operand = &operands[index];
+    if index + 1 == operands.len() {
+        return match operand {
+            ASTOperand::Register(register) => Ok(Operand2::Register { reg_id: register.register }),
+            ASTOperand::Immediate(immediate) => Ok(Operand2::Immediate { value: immediate.value }),
+            // e.g. the ':lo12:var' that is added to the page of an ADRP
+            ASTOperand::AddressOf(address_of) if address_of.lo12 => Ok(Operand2::Immediate { value: address_of.offset }),
+            _ => Err(type_mismatch(opcode, index as i32, operand,
+                                   vec![ASTOperandType::Register, ASTOperandType::Immediate]))
+        };
+    }
+
+    let reg_id = match operand {
+        ASTOperand::Register(register) => register.register,
+        _ => return Err(type_mismatch(opcode, index as i32, operand,
+                                      vec![ASTOperandType::Register]))
+    };
+
+    // only the logical instructions support ROR and only the arithmetic instructions support extensions
+    let logical = matches!(opcode, Opcode::AND | Opcode::ANDS | Opcode::ORR | Opcode::EOR | Opcode::TST | Opcode::TEQ);
+    let arithmetic = matches!(opcode, Opcode::ADD | Opcode::ADDS | Opcode::SUB | Opcode::SUBS | Opcode::RSB | Opcode::CMP);
+    let modifier = &operands[index + 1];
+    if let Some((shift_type, amount)) = shift_operand(modifier) {
+        if !(logical || arithmetic) {
+            return Err(format!("{:?} doesn't support a shift as argument nr {}", opcode, index + 2));
+        }
+
+        if shift_type == ShiftType::ROR && !logical {
+            return Err(format!("{:?} doesn't support ROR", opcode));
+        }
+
+        if amount as DWordType > reg_size - 1 {
+            return Err(format!("{:?} shift amount {} is out of range [0, {}]", opcode, amount, reg_size - 1));
+        }
+
+        return Ok(Operand2::ShiftedRegister { reg_id, shift_type, amount: amount as u8 });
+    }
+
+    if let Some((extend_type, amount)) = extend_operand(modifier) {
+        if !arithmetic {
+            return Err(format!("{:?} doesn't support an extension as argument nr {}", opcode, index + 2));
+        }
+
+        if amount > 4 {
+            return Err(format!("{:?} extend amount {} is out of range [0, 4]", opcode, amount));
+        }
+
+        return Ok(Operand2::ExtendedRegister { reg_id, extend_type, amount: amount as u8 });
+    }
+
+    match modifier {
+        ASTOperand::Shift(shift) => Err(format!("{:?} unknown shift or extension '{}'", opcode, shift.name)),
+        _ => Err(format!("{:?} doesn't support {} as argument nr {}", opcode, modifier.get_type().base_name(), index + 2))
+    }
+}
+
+// Creates the base register and the addressing mode from the memory operand at the given
+// index. The post-index addressing mode has the offset as the next operand.
+fn create_addressing_mode(opcode: Opcode, operands: &Vec<ASTOperand>, index: usize) -> Result<(RegisterType, AddressingMode), String> {
+    let post_index = operands.len() == index + 2;
+    let result = match &operands[index] {
+        ASTOperand::MemRegisterIndirect(mem_register_indirect) if post_index => {
+            let offset = match &operands[index + 1] {
+                ASTOperand::Immediate(immediate) => immediate.value as i64,
+                _ => return Err(type_mismatch(opcode, index as i32 + 1, &operands[index + 1],
+                                              vec![ASTOperandType::Immediate]))
+            };
+            (mem_register_indirect.register, AddressingMode::PostIndex { offset })
+        }
+        ASTOperand::MemRegisterIndirect(mem_register_indirect) =>
+            (mem_register_indirect.register, AddressingMode::Offset { offset: 0 }),
+        ASTOperand::MemRegIndirectWithOffset(mem) if !post_index => {
+            if mem.pre_index {
+                (mem.register, AddressingMode::PreIndex { offset: mem.offset })
+            } else {
+                (mem.register, AddressingMode::Offset { offset: mem.offset })
+            }
+        }
+        ASTOperand::MemRegIndirectWithRegOffset(mem) if !post_index =>
+            (mem.register, AddressingMode::RegisterOffset { rm: mem.offset_register, shift: mem.shift }),
+        _ => return Err(type_mismatch(opcode, index as i32, &operands[index],
+                                      vec![ASTOperandType::MemRegisterIndirect,
+                                           ASTOperandType::MemRegIndirectWithOffset,
+                                           ASTOperandType::MemRegIndirectWithRegOffset]))
+    };
+    Ok(result)
+}
+
+fn validate_addressing_mode(addressing_mode: &AddressingMode, data_type: DataType, opcode: Opcode) -> Result<(), String> {
+    let size = data_type.size() as i64;
+    match *addressing_mode {
+        // either an unscaled signed 9 bits offset or a scaled unsigned 12 bits offset
+        AddressingMode::Offset { offset } => {
+            let unscaled = (-256..=255).contains(&offset);
+            let scaled = offset % size == 0 && (0..=4095 * size).contains(&offset);
+            if !(unscaled || scaled) {
+                return Err(format!("{:?} offset {} is out of range", opcode, offset));
+            }
+        }
+        AddressingMode::PreIndex { offset } |
+        AddressingMode::PostIndex { offset } =>
+            if !(-256..=255).contains(&offset) {
+                return Err(format!("{:?} offset {} is out of range [-256, 255]", opcode, offset));
+            },
+        // the offset register can only be scaled by the size of the data
+        AddressingMode::RegisterOffset { shift, .. } =>
+            if shift != 0 && shift as u32 != size.trailing_zeros() {
+                return Err(format!("{:?} shift {} should be 0 or {}", opcode, shift, size.trailing_zeros()));
+            },
+    }
+    Ok(())
+}
+
+fn validate_pair_addressing_mode(addressing_mode: &AddressingMode, data_type: DataType, opcode: Opcode) -> Result<(), String> {
+    let size = data_type.size() as i64;
+    match *addressing_mode {
+        // a signed 7 bits offset scaled by the size of the data
+        AddressingMode::Offset { offset } |
+        AddressingMode::PreIndex { offset } |
+        AddressingMode::PostIndex { offset } =>
+            if offset % size != 0 || !(-64 * size..=63 * size).contains(&offset) {
+                return Err(format!("{:?} offset {} should be a multiple of {} in the range [{}, {}]",
+                                   opcode, offset, size, -64 * size, 63 * size));
+            },
+        AddressingMode::RegisterOffset { .. } =>
+            return Err(format!("{:?} doesn't support a register offset", opcode)),
+    }
+    Ok(())
+}
+
+// ADR and ADRP store the offset of the address relative to the PC of the instruction. The
+// address of a label is the index of the instruction and of a variable it is the offset in memory.
+fn create_pc_relative(opcode: Opcode, operands: &Vec<ASTOperand>, pc: usize, loc: SourceLocation) -> Result<Instr, String> {
+    validate_operand_count(2, operands, opcode, loc)?;
+
+    let rd = match &operands[0] {
+        ASTOperand::Register(o) if o.w_view =>
+            return Err(format!("{:?} doesn't support the W{} register", opcode, o.register)),
+        ASTOperand::Register(o) => o.register,
+        _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                      vec![ASTOperandType::Register]))
+    };
+
+    let address = match &operands[1] {
+        ASTOperand::Label(o) => o.offset,
+        ASTOperand::AddressOf(o) if !o.lo12 => o.offset,
+        _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                      vec![ASTOperandType::Label, ASTOperandType::AddressOf]))
+    };
+
+    // ADRP is relative to the 4KB page of the PC
+    let pc = pc as DWordType;
+    let offset = if opcode == Opcode::ADRP {
+        (address & !0xFFF).wrapping_sub(pc & !0xFFF)
+    } else {
+        address.wrapping_sub(pc)
+    };
+
+    Ok(Instr::DataProcessing(
+        DataProcessing {
+            opcode,
+            condition: ConditionCode::AL,
+            loc,
+            rn: None,
+            rd,
+            rd_read: false,
+            operand2: Operand2::Immediate { value: offset },
+            nzcv: 0,
+            ra: None,
+            w_view: false,
+        }
+    ))
+}
+
+// Expands 'LDR Xd, =constant' into the moves that materialize the constant.
+fn create_literal_load(operands: &Vec<ASTOperand>, loc: SourceLocation) -> Result<Vec<Instr>, String> {
+    let opcode = Opcode::LDR;
+    validate_operand_count(2, operands, opcode, loc)?;
+
+    let (rd, w_view) = match &operands[0] {
+        ASTOperand::Register(o) => (o.register, o.w_view),
+        _ => return Err(type_mismatch(opcode, 0, &operands[0],
+                                      vec![ASTOperandType::Register]))
+    };
+
+    let constant = match &operands[1] {
+        ASTOperand::Literal(o) => o.value,
+        _ => return Err(type_mismatch(opcode, 1, &operands[1],
+                                      vec![ASTOperandType::Literal]))
+    };
+
+    // a W register can be loaded with an unsigned or a sign extended 32 bits constant
+    let fits_w = u32::try_from(constant).is_ok() || i32::try_from(constant as i64).is_ok();
+    if w_view && !fits_w {
+        return Err(format!("{:?} constant {} doesn't fit in the W{} register", opcode, constant as i64, rd));
+    }
+
+    let instrs = constant_moves(constant, w_view).into_iter().map(|(opcode, value, shift)| {
+        Instr::DataProcessing(
+            DataProcessing {
+                opcode,
+                condition: ConditionCode::AL,
+                loc,
+                rn: None,
+                rd,
+                rd_read: opcode == Opcode::MOVK,
+                operand2: if opcode == Opcode::MOV {
+                    Operand2::Immediate { value }
+                } else {
+                    Operand2::ShiftedImmediate { value, shift }
+                },
+                nzcv: 0,
+                ra: None,
+                w_view,
+            }
+        )
+    }).collect();
+    Ok(instrs)
+}
+
+// The moves that materialize a constant as (opcode, immediate, shift). A single MOV if the
+// constant can be encoded, otherwise a MOVZ or MOVN followed by MOVKs. For the W view only
+// the lower 32 bits are materialized.
+fn constant_moves(constant: DWordType, w_view: bool) -> Vec<(Opcode, DWordType, u8)> {
+    let constant = if w_view { constant & 0xFFFF_FFFF } else { constant };
+    if is_mov_immediate(constant, w_view) {
+        return vec![(Opcode::MOV, constant, 0)];
+    }
+
+    let chunk_cnt = if w_view { 2 } else { 4 };
+    let chunks: Vec<DWordType> = (0..chunk_cnt).map(|k| (constant >> (16 * k)) & 0xFFFF).collect();
+    // a MOVN sets the chunks that aren't written to all ones instead of zeros
+    let inverted = chunks.iter().filter(|c| **c == 0xFFFF).count() > chunks.iter().filter(|c| **c == 0).count();
+    let skipped = if inverted { 0xFFFF } else { 0 };
+
+    let mut moves = Vec::new();
+    for (k, chunk) in chunks.into_iter().enumerate() {
+        if chunk == skipped {
+            continue;
+        }
+
+        let shift = 16 * k as u8;
+        if !moves.is_empty() {
+            moves.push((Opcode::MOVK, chunk, shift));
+        } else if inverted {
+            moves.push((Opcode::MOVN, !chunk & 0xFFFF, shift));
+        } else {
+            moves.push((Opcode::MOVZ, chunk, shift));
+        }
+    }
+    moves
+}
+
+// If MOV can encode the immediate; so as a single MOVZ, a single MOVN or a bitmask immediate.
+// For a W register the immediate is a 32 bits value, possibly written as a negative number.
+fn is_mov_immediate(value: DWordType, w_view: bool) -> bool {
+    if !w_view {
+        let is_single_chunk = |v: DWordType| (0..4).any(|k| v & !(0xFFFF << (16 * k)) == 0);
+        return is_single_chunk(value) || is_single_chunk(!value) || is_bitmask_immediate(value);
+    }
+
+    if value >> 32 != 0 && value >> 32 != 0xFFFF_FFFF {
+        return false;
+    }
+
+    let value = value & 0xFFFF_FFFF;
+    let is_single_chunk = |v: DWordType| (0..2).any(|k| v & 0xFFFF_FFFF & !(0xFFFF << (16 * k)) == 0);
+    is_single_chunk(value) || is_single_chunk(!value) || is_bitmask_immediate(value | (value << 32))
+}
+
+// A bitmask immediate is a rotated run of ones within an element of 2, 4, 8, 16, 32 or 64 bits
+// that is replicated over the register.
+fn is_bitmask_immediate(value: DWordType) -> bool {
+    if value == 0 || value == DWordType::MAX {
+        return false;
+    }
+
+    // the smallest element size the value is a replication of
+    let mut size = 64;
+    while size > 2 {
+        let half = size / 2;
+        let mask = (1 << half) - 1;
+        if value & mask != (value >> half) & mask {
+            break;
+        }
+        size = half;
+    }
+
+    let mask = if size == 64 { DWordType::MAX } else { (1 << size) - 1 };
+    let element = value & mask;
+    let rotated = ((element >> 1) | (element << (size - 1))) & mask;
+    // a single run of ones has exactly 2 transitions between a 0 and a 1
+    (element ^ rotated).count_ones() == 2
+}
+
 fn validate_operand_count(expected: usize,
                           operands: &Vec<ASTOperand>,
                           opcode: Opcode,
@@ -477,12 +1421,20 @@ pub struct SymbolScan<'a> {
 
 impl ASTVisitor for SymbolScan<'_> {
     fn visit_data(&mut self, ast_data: &mut ASTData) -> bool {
-        if self.loader.heap_limit == self.loader.cpu_config.memory_size {
+        // data is aligned to its size
+        let size = ast_data.data_type.size() as u32;
+        let offset = self.loader.heap_limit.next_multiple_of(size);
+        if offset + size > self.loader.cpu_config.memory_size {
             let loc = self.loader.to_source_location(ast_data.pos);
             self.loader.errors.push(format!("Insufficient heap to declare variable '{}' at {}:{}", ast_data.name, loc.line, loc.column));
             return false;
         }
 
+        if size < 8 && ast_data.value >> (size * 8) != 0 {
+            let loc = self.loader.to_source_location(ast_data.pos);
+            self.loader.errors.push(format!("Value {} of variable '{}' doesn't fit in {} bytes at {}:{}", ast_data.value, ast_data.name, size, loc.line, loc.column));
+        }
+
         if !is_valid_variable_name(&ast_data.name) {
             let loc = self.loader.to_source_location(ast_data.pos);
             self.loader.errors.push(format!("Illegal variable name '{}' at {}:{}", ast_data.name, loc.line, loc.column));
@@ -499,13 +1451,20 @@ impl ASTVisitor for SymbolScan<'_> {
         }
 
         self.loader.data_section.insert(ast_data.name.clone(),
-                                        Rc::new(Data { value: ast_data.value as DWordType, offset: self.loader.heap_limit as u64 }));
-        self.loader.heap_limit += 1;
+                                        Rc::new(Data { value: ast_data.value as DWordType, offset: offset as u64, data_type: ast_data.data_type }));
+        self.loader.heap_limit = offset + size;
         true
     }
 
-    fn visit_instr(&mut self, _: &mut ASTInstr) -> bool {
-        self.loader.instr_cnt += 1;
+    fn visit_instr(&mut self, ast_instr: &mut ASTInstr) -> bool {
+        // the LDR of a constant expands into multiple instructions
+        self.loader.instr_cnt += match &ast_instr.op2 {
+            ASTOperand::Literal(literal) if get_opcode(&ast_instr.mnemonic) == Some(Opcode::LDR) => {
+                let w_view = matches!(&ast_instr.op1, ASTOperand::Register(o) if o.w_view);
+                constant_moves(literal.value, w_view).len()
+            }
+            _ => 1,
+        };
         true
     }
 
@@ -534,15 +1493,17 @@ impl ASTVisitor for ProgramGeneration<'_> {
     fn visit_operand(&mut self, ast_operand: &mut ASTOperand) -> bool {
         match ast_operand {
             ASTOperand::Register(register) => {
-                if register.register >= GENERAL_ARG_REG_CNT as RegisterType {
+                // the PC is only used by the frontend
+                if register.register == PC {
                     let loc = self.loader.to_source_location(register.pos);
-                    self.loader.errors.push(format!("Unknown register r'{}' at {}:{}", register.register, loc.line, loc.column));
+                    self.loader.errors.push(format!("PC can't be used as an operand at {}:{}", loc.line, loc.column));
                     return false;
                 }
 
                 self.operand_stack.push(ast_operand.clone());
             }
-            ASTOperand::Immediate(_) => {
+            ASTOperand::Immediate(_) |
+            ASTOperand::Literal(_) => {
                 self.operand_stack.push(ast_operand.clone());
             }
             ASTOperand::Label(label) => {
@@ -551,6 +1512,31 @@ impl ASTVisitor for ProgramGeneration<'_> {
                         label.offset = *code_address as DWordType;
                         self.operand_stack.push(ast_operand.clone());
                     }
+                    // a variable without '=' is only valid for the instructions that accept an address like ADR
+                    None if self.loader.data_section.contains_key(&label.label) => {
+                        let data = self.loader.data_section.get(&label.label).unwrap();
+                        self.operand_stack.push(ASTOperand::AddressOf(ASTAddressOfOperand {
+                            label: label.label.clone(),
+                            pos: label.pos,
+                            offset: data.offset as DWordType,
+                            lo12: false,
+                        }));
+                    }
+                    // a condition code like 'eq' is parsed as a label
+                    None if get_condition_code(&label.label).is_some() => {
+                        self.operand_stack.push(ASTOperand::Condition(ASTConditionOperand {
+                            condition: get_condition_code(&label.label).unwrap(),
+                            pos: label.pos,
+                        }));
+                    }
+                    // an extension without an amount like 'uxtw' is parsed as a label
+                    None if get_extend_type(&label.label).is_some() => {
+                        self.operand_stack.push(ASTOperand::Shift(ASTShiftOperand {
+                            name: label.label.clone(),
+                            amount: 0,
+                            pos: label.pos,
+                        }));
+                    }
                     None => {
                         let loc = self.loader.to_source_location(label.pos);
                         self.loader.errors.push(format!("Unknown label '{}' at {}:{}", label.label, loc.line, loc.column));
@@ -561,7 +1547,11 @@ impl ASTVisitor for ProgramGeneration<'_> {
             ASTOperand::AddressOf(address_of) => {
                 match self.loader.data_section.get(&address_of.label) {
                     Some(data) => {
-                        address_of.offset = data.offset as DWordType;
+                        address_of.offset = if address_of.lo12 {
+                            data.offset as DWordType & 0xFFF
+                        } else {
+                            data.offset as DWordType
+                        };
                         self.operand_stack.push(ast_operand.clone());
                     }
                     None => {
@@ -573,10 +1563,13 @@ impl ASTVisitor for ProgramGeneration<'_> {
             }
 
             ASTOperand::Unused() => {}
-            ASTOperand::MemRegisterIndirect(_) => {
+            ASTOperand::MemRegisterIndirect(_) |
+            ASTOperand::MemRegIndirectWithOffset(_) |
+            ASTOperand::MemRegIndirectWithRegOffset(_) |
+            ASTOperand::Shift(_) |
+            ASTOperand::Condition(_) => {
                 self.operand_stack.push(ast_operand.clone());
             }
-            //ASTOperand::MemoryAccessWithImmediate(_, _, _) => {}
         };
 
         true
@@ -593,9 +1586,19 @@ impl ASTVisitor for ProgramGeneration<'_> {
         }
 
         let opcode = opcode_option.unwrap();
-        match create_instr(opcode, &self.operand_stack, loc) {
-            Ok(instr) => {
-                self.loader.code.push(instr);
+        let condition = get_branch_condition(&ast_instr.mnemonic).unwrap_or(ConditionCode::AL);
+        let result = if let Err(msg) = validate_special_registers(opcode, &self.operand_stack) {
+            Err(msg)
+        } else if opcode == Opcode::LDR && matches!(self.operand_stack.get(1), Some(ASTOperand::Literal(_))) {
+            create_literal_load(&self.operand_stack, loc)
+        } else if matches!(opcode, Opcode::ADR | Opcode::ADRP) {
+            create_pc_relative(opcode, &self.operand_stack, self.loader.code.len(), loc).map(|instr| vec![instr])
+        } else {
+            create_instr(opcode, condition, &self.operand_stack, loc).map(|instr| vec![instr])
+        };
+        match result {
+            Ok(instrs) => {
+                self.loader.code.extend(instrs);
             }
             Err(msg) => {
                 self.loader.errors.push(format!("{} at {}:{}", msg, loc.line, loc.column));
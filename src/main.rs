@@ -83,6 +83,10 @@ fn main() {
     if opt.stats {
         show_stats(&mut cpu);
     }
+
+    if cpu.backend.fault.is_some() {
+        exit(1);
+    }
 }
 
 fn show_stats(cpu: &CPU) {
@@ -101,8 +105,35 @@ fn show_stats(cpu: &CPU) {
     println!("-------------------- [ stats ] -------------------------");
     println!("ipc {:.2}", ipc);
     println!("branch pred {:.2}%", branch_prediction);
+    let direction_total = perf_counters.branch_direction_good_cnt + perf_counters.branch_direction_miss_cnt;
+    let direction_prediction = if direction_total != 0 {
+        100.0 * perf_counters.branch_direction_good_cnt as f32 / direction_total as f32
+    } else {
+        0.0
+    };
+
+    println!("branch predictor: {}", cpu.frontend.branch_predictor_name());
+    println!("branch direction pred {:.2}%", direction_prediction);
+    println!("branch direction good cnt: {}", perf_counters.branch_direction_good_cnt);
+    println!("branch direction miss cnt: {}", perf_counters.branch_direction_miss_cnt);
     println!("branch miss prediction cnt: {}", perf_counters.branch_miss_prediction_cnt);
     println!("branch good predictions cnt: {}", perf_counters.branch_good_predictions_cnt);
+    println!("ras hit cnt: {}", perf_counters.ras_hit_cnt);
+    println!("ras miss cnt: {}", perf_counters.ras_miss_cnt);
+    println!("ras overflow cnt: {}", perf_counters.ras_overflow_cnt);
+    println!("btb hit cnt: {}", perf_counters.btb_hit_cnt);
+    println!("btb miss cnt: {}", perf_counters.btb_miss_cnt);
+    println!("unit busy cnt: {}", perf_counters.unit_busy_cnt);
+    println!("port conflict cnt: {}", perf_counters.port_conflict_cnt);
+    for (port, name) in cpu.backend.port_names().iter().enumerate() {
+        let dispatch_cnt = perf_counters.port_dispatch_cnt[port];
+        let utilisation = if perf_counters.cycle_cnt != 0 {
+            100.0 * dispatch_cnt as f32 / perf_counters.cycle_cnt as f32
+        } else {
+            0.0
+        };
+        println!("port {} utilisation {:.2}% (dispatch cnt: {})", name, utilisation, dispatch_cnt);
+    }
     println!("decode cnt: {}", perf_counters.decode_cnt);
     println!("issue cnt: {}", perf_counters.issue_cnt);
     println!("dispatch cnt: {}", perf_counters.dispatch_cnt);
@@ -111,4 +142,11 @@ fn show_stats(cpu: &CPU) {
     println!("cycle cnt: {}", perf_counters.cycle_cnt);
     println!("bad speculation cnt: {}", perf_counters.bad_speculation_cnt);
     println!("pipeline flushes: {}", perf_counters.pipeline_flushes);
+    println!("branch squash cnt: {}", perf_counters.branch_squash_cnt);
+    println!("rob walk cnt: {}", perf_counters.rob_walk_cnt);
+    println!("load forwarded cnt: {}", perf_counters.load_forwarded_cnt);
+    println!("load blocked cnt: {}", perf_counters.load_blocked_cnt);
+    println!("memory order violation cnt: {}", perf_counters.memory_order_violation_cnt);
+    println!("acquire blocked cnt: {}", perf_counters.acquire_blocked_cnt);
+    println!("release blocked cnt: {}", perf_counters.release_blocked_cnt);
 }
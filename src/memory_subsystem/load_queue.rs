@@ -0,0 +1,180 @@
+use LQEntryState::{ALLOCATED, EXECUTED, IDLE};
+
+use crate::cpu::CPUConfig;
+use crate::instructions::instructions::DWordType;
+
+enum LQEntryState {
+    // not used.
+    IDLE,
+    // it is allocated for a load that hasn't executed yet.
+    ALLOCATED,
+    // the load has executed and has obtained its value.
+    EXECUTED,
+}
+
+struct LQEntry {
+    addr: DWordType,
+    // the number of bytes read
+    size: u8,
+    // the tail of the store buffer when the load was allocated. Every store with a
+    // sequence before the tail is older than the load.
+    sb_tail: u64,
+    // the sequence of the store the value was forwarded from. None if the value
+    // was read from memory.
+    forwarded_from: Option<u64>,
+    // set when an older store to the same address resolved after the load executed.
+    violated: bool,
+    // set for a load with acquire semantics
+    acquire: bool,
+    state: LQEntryState,
+}
+
+impl LQEntry {
+    fn reset(&mut self) {
+        self.state = IDLE;
+        self.addr = 0;
+        self.size = 0;
+        self.sb_tail = 0;
+        self.forwarded_from = None;
+        self.violated = false;
+        self.acquire = false;
+    }
+}
+
+/// The load queue keeps track of the in flight loads, so that a store that resolves
+/// its address can detect younger loads that have executed too early.
+pub(crate) struct LQ {
+    head: u64,
+    tail: u64,
+    entries: Vec<LQEntry>,
+    capacity: u16,
+}
+
+impl LQ {
+    pub(crate) fn new(cpu_config: &CPUConfig) -> LQ {
+        let mut entries = Vec::with_capacity(cpu_config.lq_capacity as usize);
+        for _ in 0..cpu_config.lq_capacity {
+            entries.push(LQEntry {
+                addr: 0,
+                size: 0,
+                sb_tail: 0,
+                forwarded_from: None,
+                violated: false,
+                acquire: false,
+                state: IDLE,
+            })
+        }
+
+        LQ {
+            capacity: cpu_config.lq_capacity,
+            head: 0,
+            tail: 0,
+            entries,
+        }
+    }
+
+    pub(crate) fn size(&self) -> u16 {
+        (self.tail - self.head) as u16
+    }
+
+    pub(crate) fn has_space(&self) -> bool {
+        self.size() < self.capacity
+    }
+
+    pub(crate) fn allocate(&mut self, sb_tail: u64, acquire: bool) -> u16 {
+        assert!(self.has_space(), "LoadQueue: can't allocate because there is no space");
+
+        let index = self.to_index(self.tail);
+        let lq_entry = &mut self.entries[index];
+        lq_entry.state = ALLOCATED;
+        lq_entry.sb_tail = sb_tail;
+        lq_entry.acquire = acquire;
+        self.tail += 1;
+        index as u16
+    }
+
+    fn to_index(&self, seq: u64) -> usize {
+        (seq % self.capacity as u64) as usize
+    }
+
+    // Records that the load has executed.
+    pub(crate) fn executed(&mut self, index: u16, addr: DWordType, size: u8, forwarded_from: Option<u64>) {
+        let lq_entry = &mut self.entries[index as usize];
+
+        match lq_entry.state {
+            ALLOCATED => {
+                lq_entry.addr = addr;
+                lq_entry.size = size;
+                lq_entry.forwarded_from = forwarded_from;
+                lq_entry.state = EXECUTED;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Called when the store with the given store buffer sequence has resolved its address.
+    // Every younger load overlapping with the store that has executed, but didn't get its value
+    // from this store or a younger one, has read a stale value.
+    pub(crate) fn store_resolved(&mut self, store_seq: u64, addr: DWordType, size: u8) {
+        for k in self.head..self.tail {
+            let index = self.to_index(k);
+            let lq_entry = &mut self.entries[index];
+
+            if let EXECUTED = lq_entry.state {
+                // the value was read from memory or forwarded from an even older store
+                let stale = match lq_entry.forwarded_from {
+                    None => true,
+                    Some(seq) => seq < store_seq,
+                };
+
+                let overlaps = lq_entry.addr < addr + size as DWordType
+                    && addr < lq_entry.addr + lq_entry.size as DWordType;
+
+                if lq_entry.sb_tail > store_seq && overlaps && stale {
+                    lq_entry.violated = true;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn sb_tail(&self, index: u16) -> u64 {
+        self.entries[index as usize].sb_tail
+    }
+
+    // If an acquire load older than the load at the given index hasn't executed yet.
+    pub(crate) fn has_pending_acquire(&self, index: u16) -> bool {
+        for k in self.head..self.tail {
+            let lq_index = self.to_index(k);
+            if lq_index == index as usize {
+                break;
+            }
+
+            let lq_entry = &self.entries[lq_index];
+            if lq_entry.acquire && matches!(lq_entry.state, ALLOCATED) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub(crate) fn is_violated(&self, index: u16) -> bool {
+        self.entries[index as usize].violated
+    }
+
+    // Removes the load at the head. Loads are deallocated in order (retire).
+    pub(crate) fn deallocate(&mut self, index: u16) {
+        debug_assert!(self.to_index(self.head) == index as usize, "LoadQueue: only the head can be deallocated");
+
+        self.entries[index as usize].reset();
+        self.head += 1;
+    }
+
+    // Removes the load at the tail; it was on a mispredicted path. Loads are squashed from young to old.
+    pub(crate) fn squash(&mut self, index: u16) {
+        debug_assert!(self.tail > self.head);
+        debug_assert!(self.to_index(self.tail - 1) == index as usize, "LoadQueue: only the tail can be squashed");
+
+        self.entries[index as usize].reset();
+        self.tail -= 1;
+    }
+}
@@ -2,26 +2,27 @@ use std::rc::Rc;
 
 use crate::cpu::CPUConfig;
 use crate::instructions::instructions::{DWordType, Program};
+use crate::memory_subsystem::load_queue::LQ;
 use crate::memory_subsystem::store_buffer::SB;
 
 pub(crate) struct MemorySubsystem {
-    pub(crate) memory: Vec<DWordType>,
+    // the memory is byte addressable
+    pub(crate) memory: Vec<u8>,
     pub(crate) sb: SB,
+    pub(crate) lq: LQ,
 }
 
 impl MemorySubsystem {
     pub fn new(cpu_config: &CPUConfig) -> MemorySubsystem {
-        let mut memory = Vec::with_capacity(cpu_config.memory_size as usize);
-
-        for _ in 0..cpu_config.memory_size {
-            memory.push(0);
-        }
+        let memory = vec![0; cpu_config.memory_size as usize];
 
         let sb = SB::new(cpu_config);
+        let lq = LQ::new(cpu_config);
 
         MemorySubsystem {
             memory,
             sb,
+            lq,
         }
     }
 
@@ -31,7 +32,7 @@ impl MemorySubsystem {
         }
 
         for data in program.data_items.values() {
-            self.memory[data.offset as usize] = data.value;
+            write(&mut self.memory, data.offset, data.data_type.size(), data.value);
         }
     }
 
@@ -39,3 +40,42 @@ impl MemorySubsystem {
         self.sb.do_cycle(&mut self.memory);
     }
 }
+
+// Reads 'size' bytes at the given address; the bytes are stored in little endian order.
+pub(crate) fn read(memory: &[u8], addr: DWordType, size: u8) -> DWordType {
+    let addr = addr as usize;
+    let mut value = 0;
+    for k in (0..size as usize).rev() {
+        value = (value << 8) | memory[addr + k] as DWordType;
+    }
+    value
+}
+
+// Writes the lowest 'size' bytes of the value at the given address in little endian order.
+pub(crate) fn write(memory: &mut [u8], addr: DWordType, size: u8, value: DWordType) {
+    let addr = addr as usize;
+    for k in 0..size as usize {
+        memory[addr + k] = (value >> (8 * k)) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_write() {
+        let mut memory = vec![0u8; 16];
+
+        write(&mut memory, 0, 8, 0x0102030405060708);
+        assert_eq!(memory[0], 0x08);
+        assert_eq!(memory[7], 0x01);
+        assert_eq!(read(&memory, 0, 8), 0x0102030405060708);
+        assert_eq!(read(&memory, 0, 4), 0x05060708);
+        assert_eq!(read(&memory, 4, 2), 0x0304);
+        assert_eq!(read(&memory, 7, 1), 0x01);
+
+        write(&mut memory, 8, 2, 0xFFFF_1234);
+        assert_eq!(read(&memory, 8, 8), 0x1234);
+    }
+}
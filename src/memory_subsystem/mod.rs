@@ -1,2 +1,3 @@
 pub mod memory_subsystem;
-mod store_buffer;
\ No newline at end of file
+pub(crate) mod store_buffer;
+pub(crate) mod load_queue;
\ No newline at end of file
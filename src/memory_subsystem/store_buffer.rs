@@ -2,6 +2,7 @@ use SBEntryState::{ALLOCATED, COMMITTED, IDLE, READY};
 
 use crate::cpu::CPUConfig;
 use crate::instructions::instructions::DWordType;
+use crate::memory_subsystem::memory_subsystem::write;
 
 enum SBEntryState {
     // not used.
@@ -16,16 +17,37 @@ enum SBEntryState {
     COMMITTED,
 }
 
+/// The outcome of searching the store buffer on behalf of a load.
+pub(crate) enum SBLookup {
+    // an older store containing all bytes of the load was found; its value can be forwarded.
+    Forward {
+        value: DWordType,
+        // the sequence of the store the value is forwarded from
+        seq: u64,
+    },
+    // an older store has not yet resolved its address or only partially overlaps
+    // with the load, so the load can't be safely performed.
+    Blocked,
+    // none of the older stores matches the address; the load can read from memory.
+    Miss,
+}
+
 struct SBEntry {
     value: DWordType,
     addr: DWordType,
+    // the number of bytes written
+    size: u8,
+    // set for a store with release semantics
+    release: bool,
     state: SBEntryState,
 }
 
 impl SBEntry {
     fn reset(&mut self) {
         self.state = IDLE;
+        self.release = false;
         self.addr = 0;
+        self.size = 0;
         self.value = 0;
     }
 }
@@ -40,11 +62,16 @@ pub(crate) struct SB {
 
 impl SB {
     pub(crate) fn new(cpu_config: &CPUConfig) -> SB {
+        // a store pair occupies 2 slots
+        assert!(cpu_config.sb_capacity >= 2, "StoreBuffer: the capacity must be at least 2");
+
         let mut entries = Vec::with_capacity(cpu_config.sb_capacity as usize);
         for _ in 0..cpu_config.sb_capacity {
             entries.push(SBEntry {
                 value: 0,
                 addr: 0,
+                size: 0,
+                release: false,
                 state: IDLE,
             })
         }
@@ -70,11 +97,23 @@ impl SB {
         return self.size() < self.capacity;
     }
 
-    pub(crate) fn allocate(&mut self) -> u16 {
+    // If the given number of stores can be allocated; a store pair needs 2 slots.
+    pub(crate) fn has_space_for(&self, cnt: u16) -> bool {
+        return self.size() + cnt <= self.capacity;
+    }
+
+    // The current tail. Every store with a sequence before the tail is older than
+    // an instruction that observes this tail.
+    pub(crate) fn tail(&self) -> u64 {
+        self.tail
+    }
+
+    pub(crate) fn allocate(&mut self, release: bool) -> u16 {
         assert!(self.has_space(), "StoreBuffer: can't allocate because there is no space");
 
         let index = self.to_index(self.tail);
         self.entries[index].state = ALLOCATED;
+        self.entries[index].release = release;
         self.tail += 1;
         return index as u16;
     }
@@ -83,12 +122,13 @@ impl SB {
         (seq % self.capacity as u64) as usize
     }
 
-    pub(crate) fn store(&mut self, index: u16, addr: DWordType, value: DWordType) {
+    pub(crate) fn store(&mut self, index: u16, addr: DWordType, size: u8, value: DWordType) {
         let sb_entry = &mut self.entries[index as usize];
 
         match sb_entry.state {
             ALLOCATED => {
                 sb_entry.addr = addr;
+                sb_entry.size = size;
                 sb_entry.value = value;
                 sb_entry.state = READY;
             }
@@ -96,6 +136,57 @@ impl SB {
         }
     }
 
+    // The sequence of the store at the given index.
+    pub(crate) fn to_seq(&self, index: u16) -> u64 {
+        let head_index = self.to_index(self.head) as u64;
+        let capacity = self.capacity as u64;
+        self.head + (index as u64 + capacity - head_index) % capacity
+    }
+
+    // Searches the stores that are older than a load for the given address range. The load
+    // observed the tail 'sb_tail' when it was allocated, so only the stores before
+    // that tail are considered. The search starts at the youngest older store so
+    // that the most recent value is forwarded.
+    //
+    // If speculative is set, stores with an unknown address are assumed not to alias
+    // with the load. The load queue will detect if that assumption was wrong.
+    pub(crate) fn lookup(&self, sb_tail: u64, addr: DWordType, size: u8, speculative: bool) -> SBLookup {
+        let end = addr + size as DWordType;
+        for k in (self.head..sb_tail).rev() {
+            let sb_entry = &self.entries[self.to_index(k)];
+            match sb_entry.state {
+                ALLOCATED => if !speculative {
+                    return SBLookup::Blocked;
+                },
+                READY |
+                COMMITTED => {
+                    let sb_end = sb_entry.addr + sb_entry.size as DWordType;
+                    if sb_entry.addr <= addr && end <= sb_end {
+                        // the store contains all bytes of the load
+                        let shift = 8 * (addr - sb_entry.addr);
+                        let value = if size == 8 {
+                            sb_entry.value
+                        } else {
+                            (sb_entry.value >> shift) & ((1 << (8 * size as DWordType)) - 1)
+                        };
+                        return SBLookup::Forward { value, seq: k };
+                    } else if sb_entry.addr < end && addr < sb_end {
+                        // the load needs to wait till the store has been written to memory
+                        return SBLookup::Blocked;
+                    }
+                }
+                IDLE => unreachable!(),
+            }
+        }
+
+        SBLookup::Miss
+    }
+
+    // If any of the stores before the tail is a release store that hasn't been written to memory.
+    pub(crate) fn has_release(&self, sb_tail: u64) -> bool {
+        (self.head..sb_tail).any(|k| self.entries[self.to_index(k)].release)
+    }
+
     pub(crate) fn commit(&mut self, index: u16) {
         let sb_entry = &mut self.entries[index as usize];
 
@@ -105,29 +196,26 @@ impl SB {
         }
     }
 
-    pub(crate) fn flush(&mut self) {
-        // to flush, we go backwards from the tail and 'deallocate' every store
-        // until a committed store is found. Behind that committed store can only
-        // be other committed stores because commits are done in order (retire).
+    // Deallocates the store at the given index; it was on a mispredicted path. Stores are
+    // squashed from young to old, so it must be the youngest store.
+    pub(crate) fn squash(&mut self, index: u16) {
+        debug_assert!(self.tail > self.head);
+        debug_assert!(self.to_index(self.tail - 1) == index as usize, "StoreBuffer: only the tail can be squashed");
 
-        for k in (self.head..self.tail).rev() {
-            let index = self.to_index(k);
-            let sb_entry = &mut self.entries[index];
-            match sb_entry.state {
-                ALLOCATED |
-                READY => {
-                    sb_entry.reset();
-                    self.tail -= 1;
-                }
-                COMMITTED => {
-                    break;
-                }
-                _ => unreachable!(),
+        let sb_entry = &mut self.entries[index as usize];
+        match sb_entry.state {
+            ALLOCATED |
+            READY => {
+                sb_entry.reset();
+                self.tail -= 1;
             }
+            _ => unreachable!(),
         }
     }
 
-    pub(crate) fn do_cycle(&mut self, memory: &mut Vec<DWordType>) {
+    // Writes the committed stores to memory. The stores are written in order, so a release store
+    // only becomes visible after all older stores.
+    pub(crate) fn do_cycle(&mut self, memory: &mut [u8]) {
         for _ in 0..self.lfb_count {
             if self.is_empty() {
                 break;
@@ -140,7 +228,7 @@ impl SB {
                 READY => {}
                 COMMITTED => {
                     // write the store to memory
-                    memory[sb_entry.addr as usize] = sb_entry.value;
+                    write(memory, sb_entry.addr, sb_entry.size, sb_entry.value);
                     sb_entry.reset();
                     self.head += 1;
                 }